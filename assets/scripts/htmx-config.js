@@ -3,3 +3,22 @@ document.body.addEventListener("htmx:responseError", function (event) {
     window.location.reload();
   }
 });
+
+// error responses carry an out of band toast instead of content
+document.body.addEventListener("htmx:beforeSwap", function (event) {
+  const status = event.detail.xhr.status;
+
+  if (status >= 400 && status !== 401) {
+    event.detail.shouldSwap = true;
+    event.detail.isError = false;
+  }
+});
+
+document.body.addEventListener("htmx:oobAfterSwap", function (event) {
+  if (event.detail.target.id !== "toasts") {
+    return;
+  }
+
+  const toast = event.detail.target.lastElementChild;
+  setTimeout(() => toast?.remove(), 5000);
+});
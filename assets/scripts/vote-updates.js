@@ -1,30 +1,94 @@
+// the page says what it listens to with data attributes on the script tag
+const { event, topics } = document.currentScript.dataset;
+
+const handlers = {
+  // the views of the results on this page fetch themselves again
+  results_changed: () => htmx.trigger(document.body, "results-changed"),
+  song_hidden: (update) => removeSong(update.song_id),
+  song_deleted: (update) => removeSong(update.song_id),
+  song_unhidden: () => refreshSongs(),
+  setlist_changed: () => refreshSongs(),
+  voting_closed: () => {
+    const element = document.getElementById("voting-window");
+
+    if (element) {
+      element.className = "px-4 pt-2 text-center text-red-500";
+      element.textContent = "Afstemningen er lukket, der kan ikke længere stemmes";
+    }
+  },
+  now_playing: (update) => {
+    const element = document.getElementById("now-playing");
+
+    if (element) {
+      element.textContent = update.song ? `Spiller nu: ${update.song.title}` : "";
+    }
+  },
+};
+
+function removeSong(songId) {
+  document.getElementById(`song-${songId}`)?.remove();
+}
+
+// the server renders the cards, since they show this guest's votes
+function refreshSongs() {
+  if (document.getElementById("songs")) {
+    htmx.ajax("GET", `/e/${event}/songs`, { target: "#songs", swap: "outerHTML" });
+  }
+}
+
+function handle(e) {
+  const update = JSON.parse(e.data);
+
+  handlers[update.kind]?.(update);
+}
+
+// falls back to Server-Sent Events, which reconnect on their own
+function listen() {
+  console.log("Listening for server-sent events");
+
+  const params = new URLSearchParams({ event });
+  for (const topic of topics.split(" ")) {
+    params.append("topics", topic);
+  }
+
+  const events = new EventSource(`/events?${params}`);
+  events.onmessage = handle;
+}
+
 function subscribe() {
   console.log("Connecting");
 
-  const startTime = Date.now();
+  let opened = false;
 
   const scheme = location.protocol.startsWith("https") ? "wss" : "ws";
   const websocket = new WebSocket(
     `${scheme}://${window.location.host}/websocket`,
   );
 
-  websocket.onmessage = (e) => {
-    const timeSinceStart = Date.now() - startTime;
-
-    // skip re proccessing start message
-    if (timeSinceStart < 500) {
-      return;
-    }
-
-    htmx.swap("#vote-results", e.data, { swapStyle: "outerHTML" });
-  };
+  websocket.onmessage = handle;
 
   websocket.onopen = () => {
     console.log("Connection opened");
+    opened = true;
+
+    websocket.send(
+      JSON.stringify({
+        action: "subscribe",
+        topics: topics.split(" "),
+        event,
+      }),
+    );
   };
 
   websocket.onclose = () => {
     console.log("Disconnected");
+
+    // the network does not let websockets through
+    if (!opened) {
+      listen();
+      return;
+    }
+
     setTimeout(() => subscribe(), 10000);
   };
 }
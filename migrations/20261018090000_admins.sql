@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS admins (
+    id serial PRIMARY KEY NOT NULL UNIQUE,
+    username text NOT NULL UNIQUE,
+    password_hash text NOT NULL
+);
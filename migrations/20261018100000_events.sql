@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS events (
+    id serial PRIMARY KEY NOT NULL UNIQUE,
+    slug text NOT NULL UNIQUE,
+    name text NOT NULL,
+    date date NOT NULL DEFAULT CURRENT_DATE,
+    venue text,
+    open boolean NOT NULL DEFAULT TRUE
+);
+
+INSERT INTO events (slug, name) VALUES ('festorkestret', 'Festorkestret');
+
+ALTER TABLE votes ADD COLUMN event_id int REFERENCES events(id) ON DELETE CASCADE;
+UPDATE votes SET event_id = (SELECT id FROM events WHERE slug = 'festorkestret');
+ALTER TABLE votes ALTER COLUMN event_id SET NOT NULL;
+
+ALTER TABLE votes DROP CONSTRAINT unique_session_song;
+ALTER TABLE votes ADD CONSTRAINT unique_event_session_song UNIQUE (event_id, session_id, song_id);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS event_songs (
+    event_id int NOT NULL,
+    song_id int NOT NULL,
+    PRIMARY KEY (event_id, song_id),
+    FOREIGN KEY (event_id) REFERENCES events(id) ON DELETE CASCADE,
+    FOREIGN KEY (song_id) REFERENCES songs(id) ON DELETE CASCADE
+);
+
+INSERT INTO event_songs (event_id, song_id)
+SELECT e.id, s.id FROM events e CROSS JOIN songs s;
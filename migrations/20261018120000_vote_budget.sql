@@ -0,0 +1,2 @@
+ALTER TABLE events
+ADD COLUMN vote_budget int NOT NULL DEFAULT 5;
@@ -0,0 +1,14 @@
+ALTER TABLE events
+ADD COLUMN voting_mode text NOT NULL DEFAULT 'approval'
+CHECK (voting_mode IN ('approval', 'borda', 'instant_runoff'));
+
+-- the rank of a vote is the order the guest picked the song in
+ALTER TABLE votes
+ADD COLUMN rank int NOT NULL DEFAULT 1;
+
+UPDATE votes v SET rank = ranked.rank
+FROM (
+    SELECT id, row_number() OVER (PARTITION BY event_id, session_id ORDER BY id) AS rank
+    FROM votes
+) ranked
+WHERE v.id = ranked.id;
@@ -0,0 +1,3 @@
+ALTER TABLE events
+ADD COLUMN voting_open boolean NOT NULL DEFAULT TRUE,
+ADD COLUMN closes_at timestamptz;
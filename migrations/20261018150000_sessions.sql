@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS sessions (
+    id text PRIMARY KEY NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+INSERT INTO sessions (id) SELECT DISTINCT session_id FROM votes;
+
+ALTER TABLE votes
+ADD CONSTRAINT votes_session_id_fkey FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE;
@@ -0,0 +1,14 @@
+ALTER TABLE sessions
+ADD COLUMN ip text;
+
+ALTER TABLE events
+ADD COLUMN max_sessions_per_ip int;
+
+CREATE TABLE IF NOT EXISTS rejected_votes (
+    id serial PRIMARY KEY NOT NULL,
+    event_id int NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    session_id text NOT NULL,
+    ip text,
+    reason text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,9 @@
+ALTER TABLE events
+ADD COLUMN join_code text;
+
+CREATE TABLE IF NOT EXISTS event_admissions (
+    event_id int NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    session_id text NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (event_id, session_id)
+);
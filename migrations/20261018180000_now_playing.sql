@@ -0,0 +1,2 @@
+ALTER TABLE events
+ADD COLUMN now_playing int REFERENCES songs(id) ON DELETE SET NULL;
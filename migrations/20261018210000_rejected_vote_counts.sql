@@ -0,0 +1,2 @@
+-- rejections are counted in memory and saved as one row per session and reason
+ALTER TABLE rejected_votes ADD COLUMN count integer NOT NULL DEFAULT 1;
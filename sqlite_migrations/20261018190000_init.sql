@@ -0,0 +1,71 @@
+-- the SQLite schema matches the Postgres one after all of ./migrations
+
+CREATE TABLE IF NOT EXISTS songs (
+    id integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    artist text NOT NULL,
+    title text NOT NULL,
+    description text,
+    hidden boolean NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS admins (
+    id integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    username text NOT NULL UNIQUE,
+    password_hash text NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS events (
+    id integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    slug text NOT NULL UNIQUE,
+    name text NOT NULL,
+    date text NOT NULL DEFAULT CURRENT_DATE,
+    venue text,
+    open boolean NOT NULL DEFAULT TRUE,
+    vote_budget integer NOT NULL DEFAULT 5,
+    voting_mode text NOT NULL DEFAULT 'approval'
+        CHECK (voting_mode IN ('approval', 'borda', 'instant_runoff')),
+    voting_open boolean NOT NULL DEFAULT TRUE,
+    closes_at text,
+    max_sessions_per_ip integer,
+    join_code text,
+    now_playing integer REFERENCES songs(id) ON DELETE SET NULL
+);
+
+INSERT INTO events (slug, name) VALUES ('festorkestret', 'Festorkestret');
+
+CREATE TABLE IF NOT EXISTS event_songs (
+    event_id integer NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    song_id integer NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+    PRIMARY KEY (event_id, song_id)
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id text PRIMARY KEY NOT NULL,
+    created_at text NOT NULL,
+    ip text
+);
+
+CREATE TABLE IF NOT EXISTS votes (
+    id integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    session_id text NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    song_id integer NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+    event_id integer NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    rank integer NOT NULL DEFAULT 1,
+    UNIQUE (event_id, session_id, song_id)
+);
+
+CREATE TABLE IF NOT EXISTS rejected_votes (
+    id integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    event_id integer NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    session_id text NOT NULL,
+    ip text,
+    reason text NOT NULL,
+    created_at text NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS event_admissions (
+    event_id integer NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    session_id text NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    created_at text NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (event_id, session_id)
+);
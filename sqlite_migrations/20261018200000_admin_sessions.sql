@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS admin_sessions (
+    id text PRIMARY KEY NOT NULL,
+    admin_id integer NOT NULL REFERENCES admins(id) ON DELETE CASCADE,
+    expires_at text NOT NULL
+);
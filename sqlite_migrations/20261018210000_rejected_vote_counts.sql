@@ -0,0 +1 @@
+ALTER TABLE rejected_votes ADD COLUMN count integer NOT NULL DEFAULT 1;
@@ -0,0 +1,240 @@
+use crate::{
+    api::ApiError,
+    app::AppState,
+    config::AdminConfig,
+    database::Database,
+    errors::AppError,
+    html,
+    page::page,
+    rate_limit::{ClientIp, RateLimiter},
+    session::generate_session_id,
+    view::View,
+};
+use anyhow::Result;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    SignedCookieJar,
+};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration as StdDuration};
+use tracing::{info, warn};
+
+const ADMIN_COOKIE: &str = "admin_session";
+/// How long an admin stays logged in, both in the cookie and on the server.
+const ADMIN_SESSION_HOURS: i64 = 12;
+/// Logins allowed per IP address each minute, so passwords cannot be guessed quickly.
+const LOGIN_ATTEMPTS_PER_MINUTE: u32 = 10;
+
+#[derive(Deserialize, Debug)]
+pub struct LoginInput {
+    username: String,
+    password: String,
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| anyhow::anyhow!("Failed to hash password: {}", err))?;
+
+    Ok(hash.to_string())
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Creates the configured admin if it does not exist yet.
+/// Counts the logins from each IP address.
+pub fn login_rate_limiter() -> RateLimiter {
+    RateLimiter::new(LOGIN_ATTEMPTS_PER_MINUTE, StdDuration::from_secs(60))
+}
+
+pub async fn bootstrap_admin(database: &Database, admin: &AdminConfig) -> Result<()> {
+    let (Some(username), Some(password)) = (&admin.username, &admin.password) else {
+        return Ok(());
+    };
+
+    if database.get_admin_by_username(username).await?.is_none() {
+        database
+            .create_admin(username, &hash_password(password)?)
+            .await?;
+        info!("Created admin {}", username);
+    }
+
+    Ok(())
+}
+
+pub async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let jar = SignedCookieJar::from_headers(request.headers(), state.key.clone());
+    let session = match jar.get(ADMIN_COOKIE) {
+        Some(cookie) => state.database.get_admin_session(cookie.value()).await?,
+        None => None,
+    };
+
+    let admin = match session {
+        Some(session) if !session.has_expired() => {
+            state.database.get_admin(session.admin_id).await?
+        }
+        Some(session) => {
+            state.database.delete_admin_session(&session.id).await?;
+            None
+        }
+        None => None,
+    };
+
+    if admin.is_some() {
+        return Ok(next.run(request).await);
+    }
+
+    if request.uri().path().starts_with("/api/") {
+        Ok(ApiError::from(AppError::Unauthorized).into_response())
+    } else if request.headers().contains_key("HX-Request") {
+        Err(AppError::Unauthorized)
+    } else {
+        Ok(Redirect::to("/login").into_response())
+    }
+}
+
+pub async fn login_page() -> View {
+    page(login_form(None), "Log ind")
+}
+
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Form(input): Form<LoginInput>,
+) -> Result<Response, AppError> {
+    if let Some(ip) = ip.filter(|ip| !state.login_attempts.check(ip)) {
+        warn!("Too many logins from {}", ip);
+        let form = login_form(Some(
+            "For mange forsøg på at logge ind, vent et minut og prøv igen",
+        ));
+        return Ok((StatusCode::TOO_MANY_REQUESTS, page(form, "Log ind")).into_response());
+    }
+
+    let jar = SignedCookieJar::from_headers(&headers, state.key.clone());
+    let admin = state
+        .database
+        .get_admin_by_username(&input.username)
+        .await?
+        .filter(|admin| verify_password(&input.password, &admin.password_hash));
+
+    let Some(admin) = admin else {
+        warn!("Failed login for {}", input.username);
+        let form = login_form(Some("Forkert brugernavn eller adgangskode"));
+        return Ok(page(form, "Log ind").into_response());
+    };
+
+    let session = state
+        .database
+        .create_admin_session(
+            &generate_session_id(),
+            admin.id,
+            Utc::now() + Duration::hours(ADMIN_SESSION_HOURS),
+        )
+        .await?;
+    info!("Admin {} logged in", admin.username);
+
+    let cookie = Cookie::build((ADMIN_COOKIE, session.id))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .max_age(time::Duration::hours(ADMIN_SESSION_HOURS));
+
+    Ok((jar.add(cookie), Redirect::to("/setlist")).into_response())
+}
+
+/// Deletes the session on the server as well, so a copy of the cookie stops working too.
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(SignedCookieJar, Redirect), AppError> {
+    let jar = SignedCookieJar::from_headers(&headers, state.key.clone());
+    if let Some(cookie) = jar.get(ADMIN_COOKIE) {
+        state.database.delete_admin_session(cookie.value()).await?;
+    }
+
+    let jar = jar.remove(Cookie::build(ADMIN_COOKIE).path("/"));
+
+    Ok((jar, Redirect::to("/login")))
+}
+
+fn login_form(error: Option<&str>) -> View {
+    html! {
+        <form
+            method="post"
+            action="/login"
+            class="flex flex-col gap-3 p-4 w-full max-w-lg rounded-lg border shadow dark:border-neutral-700 dark:bg-neutral-950"
+        >
+            <h1 class="text-lg">Log ind</h1>
+            {match error {
+                Some(error) => html! { <p class="text-red-500">{error}</p> },
+                None => Default::default(),
+            }}
+            <label for="username">Brugernavn:</label>
+            <input
+                class="p-1 rounded bg-neutral-300 text-black"
+                type="text"
+                id="username"
+                name="username"
+                autocomplete="username"
+            />
+            <label for="password">Adgangskode:</label>
+            <input
+                class="p-1 rounded bg-neutral-300 text-black"
+                type="password"
+                id="password"
+                name="password"
+                autocomplete="current-password"
+            />
+            <input
+                class="p-1 text-white bg-blue-500 rounded transition-colors hover:bg-blue-400"
+                type="submit"
+                value="Log ind"
+            />
+        </form>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwords_only_match_their_own_hash() {
+        let hash = hash_password("hunter2").unwrap();
+
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+        assert_ne!(hash, hash_password("hunter2").unwrap());
+    }
+
+    #[test]
+    fn malformed_hashes_never_match() {
+        assert!(!verify_password("hunter2", "hunter2"));
+        assert!(!verify_password("", ""));
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    app::AppState,
+    database::{Event, Song, Vote, VoteResult},
+    errors::{AppError, BadRequestError},
+    events::event_by_slug,
+    join::ensure_admitted,
+    rate_limit::{limit_join_attempts, limit_votes, ClientIp},
+    session::{remember_me, Session},
+    updates::{publish, Update},
+    vote_results::{song_visibility_changed, votes_updated},
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, warn};
+
+/// Renders an `AppError` as a JSON body instead of an htmx toast.
+pub struct ApiError(AppError);
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+impl From<AppError> for ApiError {
+    fn from(err: AppError) -> Self {
+        ApiError(err)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        ApiError(AppError::from(err))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if let AppError::Database(err) = &self.0 {
+            error!("Database error: {:?}", err);
+        }
+
+        (
+            self.0.status(),
+            Json(ErrorBody {
+                error: self.0.to_string(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+#[derive(Deserialize, Debug)]
+pub struct SongInput {
+    artist: String,
+    title: String,
+    description: Option<String>,
+}
+
+impl SongInput {
+    fn validate(&self) -> ApiResult<()> {
+        if self.artist.trim().is_empty() || self.title.trim().is_empty() {
+            return Err(AppError::Validation(
+                "Sangen skal have en titel og en kunstner".to_string(),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct JoinInput {
+    code: String,
+}
+
+#[derive(Serialize)]
+pub struct SessionVotes {
+    votes: Vec<Vote>,
+    vote_budget: i32,
+}
+
+/// Routes under `/api/v1` that anyone can use.
+pub fn public_routes(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/songs", get(list_songs))
+        .route("/songs/:id", get(get_song))
+        .route("/events", get(list_events))
+        .route("/events/:slug", get(get_event))
+        .route(
+            "/session",
+            post(start_session)
+                .route_layer(middleware::from_fn_with_state(state.clone(), remember_me)),
+        )
+        .route("/events/:slug/join", post(join_event))
+        .route("/events/:slug/votes", get(list_votes))
+        .route(
+            "/events/:slug/votes/:song_id",
+            post(create_vote)
+                .delete(delete_vote)
+                .route_layer(middleware::from_fn_with_state(state.clone(), limit_votes)),
+        )
+        .route("/events/:slug/results", get(vote_results))
+}
+
+/// Routes under `/api/v1` that need an admin session.
+pub fn admin_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/songs", post(create_song))
+        .route("/songs/:id", put(update_song).delete(delete_song))
+        .route("/songs/:id/hide", put(hide_song))
+        .route("/songs/:id/unhide", put(unhide_song))
+        .route("/events/:slug/votes", axum::routing::delete(clear_votes))
+}
+
+/// Gives scripts and apps the session cookie that guests get from the vote page, which the
+/// join and vote routes need.
+async fn start_session(session: Session) -> StatusCode {
+    match session.is_new {
+        true => StatusCode::CREATED,
+        false => StatusCode::NO_CONTENT,
+    }
+}
+
+async fn find_event(state: &AppState, slug: &str) -> ApiResult<Event> {
+    Ok(event_by_slug(&state.database, slug).await?)
+}
+
+async fn list_songs(State(state): State<Arc<AppState>>) -> ApiResult<Json<Vec<Song>>> {
+    Ok(Json(state.database.get_setlist().await?))
+}
+
+async fn get_song(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> ApiResult<Json<Song>> {
+    state
+        .database
+        .get_song(id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound("Sangen findes ikke".to_string()).into())
+}
+
+async fn create_song(
+    State(state): State<Arc<AppState>>,
+    Json(input): Json<SongInput>,
+) -> ApiResult<(StatusCode, Json<Song>)> {
+    input.validate()?;
+
+    let song = state
+        .database
+        .add_song(&input.artist, &input.title, input.description.as_deref())
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(song)))
+}
+
+async fn update_song(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+    Json(input): Json<SongInput>,
+) -> ApiResult<Json<Song>> {
+    input.validate()?;
+
+    let song = state
+        .database
+        .update_song(
+            id,
+            &input.artist,
+            &input.title,
+            input.description.as_deref(),
+        )
+        .await?;
+
+    Ok(Json(song))
+}
+
+async fn delete_song(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> ApiResult<StatusCode> {
+    warn!("Deleting song {}", id);
+    state.database.delete_song(id).await?;
+
+    publish(&state.tx, Update::SongDeleted { song_id: id });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn hide_song(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> ApiResult<Json<Song>> {
+    warn!("Hiding song {}", id);
+    let song = state.database.hide_song(id).await?;
+
+    publish(&state.tx, Update::SongHidden { song_id: song.id });
+    song_visibility_changed(&state.tx, &state.database, song.id).await;
+
+    Ok(Json(song))
+}
+
+async fn unhide_song(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i32>,
+) -> ApiResult<Json<Song>> {
+    warn!("Unhiding song {}", id);
+    let song = state.database.unhide_song(id).await?;
+
+    publish(&state.tx, Update::SongUnhidden { song: song.clone() });
+    song_visibility_changed(&state.tx, &state.database, song.id).await;
+
+    Ok(Json(song))
+}
+
+async fn list_events(State(state): State<Arc<AppState>>) -> ApiResult<Json<Vec<Event>>> {
+    Ok(Json(state.database.get_events().await?))
+}
+
+async fn get_event(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> ApiResult<Json<Event>> {
+    Ok(Json(find_event(&state, &slug).await?))
+}
+
+async fn join_event(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+    session: Session,
+    ClientIp(ip): ClientIp,
+    Json(input): Json<JoinInput>,
+) -> ApiResult<StatusCode> {
+    let session_id = session.existing()?;
+    let event = find_event(&state, &slug).await?;
+
+    limit_join_attempts(&state.vote_guard, &event, &session, ip.as_deref())?;
+
+    if !event.join_code_matches(&input.code) {
+        warn!("Wrong join code for {} by {}", event.slug, session_id);
+        return Err(AppError::BadRequest(BadRequestError::NotAdmitted).into());
+    }
+
+    state.database.admit_session(event.id, session_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_votes(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+    session: Session,
+) -> ApiResult<Json<SessionVotes>> {
+    let session_id = &session.id;
+    let event = find_event(&state, &slug).await?;
+
+    let votes = state.database.get_votes(event.id, session_id).await?;
+
+    Ok(Json(SessionVotes {
+        votes,
+        vote_budget: event.vote_budget,
+    }))
+}
+
+async fn create_vote(
+    State(state): State<Arc<AppState>>,
+    Path((slug, song_id)): Path<(String, i32)>,
+    session: Session,
+) -> ApiResult<(StatusCode, Json<Song>)> {
+    let session_id = session.existing()?;
+    let event = find_event(&state, &slug).await?;
+
+    warn!(
+        "New vote for song {} by {} at {}",
+        song_id, session_id, event.slug
+    );
+
+    if !event.accepts_votes() {
+        return Err(AppError::BadRequest(BadRequestError::VotingClosed).into());
+    }
+
+    ensure_admitted(&state.database, &event, session_id).await?;
+
+    let (song, _) = state
+        .database
+        .create_vote(event.id, session_id, song_id, event.vote_budget)
+        .await?;
+
+    votes_updated(&state.tx, &state.database, &event).await;
+
+    Ok((StatusCode::CREATED, Json(song)))
+}
+
+async fn delete_vote(
+    State(state): State<Arc<AppState>>,
+    Path((slug, song_id)): Path<(String, i32)>,
+    session: Session,
+) -> ApiResult<StatusCode> {
+    let session_id = session.existing()?;
+    let event = find_event(&state, &slug).await?;
+
+    warn!(
+        "Delete vote for song {} by {} at {}",
+        song_id, session_id, event.slug
+    );
+
+    if !event.accepts_votes() {
+        return Err(AppError::BadRequest(BadRequestError::VotingClosed).into());
+    }
+
+    ensure_admitted(&state.database, &event, session_id).await?;
+
+    state
+        .database
+        .delete_vote(event.id, session_id, song_id)
+        .await?;
+
+    votes_updated(&state.tx, &state.database, &event).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn vote_results(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> ApiResult<Json<Vec<VoteResult>>> {
+    let event = find_event(&state, &slug).await?;
+
+    Ok(Json(state.database.get_vote_results(&event).await?))
+}
+
+async fn clear_votes(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> ApiResult<StatusCode> {
+    let event = find_event(&state, &slug).await?;
+
+    warn!("Clearing votes for event {}", event.id);
+    state.database.clear_votes(event.id).await?;
+
+    votes_updated(&state.tx, &state.database, &event).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
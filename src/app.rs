@@ -1,159 +1,214 @@
+use anyhow::Context;
 use axum::{
-    extract::{
-        ws::{Message, WebSocket},
-        Request, State, WebSocketUpgrade,
-    },
-    middleware::{self, Next},
-    response::{IntoResponse, Response},
+    extract::State,
+    middleware,
+    response::IntoResponse,
     routing::{delete, get, post, put},
     Router,
 };
-use axum_extra::extract::{
-    cookie::{Cookie, SameSite},
-    CookieJar,
-};
-use dotenv::dotenv;
-use futures::{sink::SinkExt, stream::StreamExt};
-use random_string::{charsets, generate};
-use std::{env, sync::Arc};
-use tokio::sync::watch::{channel, Receiver, Sender};
+use axum_extra::extract::cookie::Key;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::sync::broadcast::{channel, Sender};
 use tower_http::{services::ServeDir, trace};
-use tracing::info;
+use tracing::warn;
 
 use crate::{
-    database::{Credentials, Database},
+    admin::{bootstrap_admin, login, login_page, login_rate_limiter, logout, require_admin},
+    api,
+    config::Config,
+    database::Database,
+    errors::{error_pages, AppError},
+    events::{
+        add_event, clear_votes, close_event, close_expired_voting, delete_event, event_page,
+        events_page, open_event, update_event_setlist, update_event_settings, update_now_playing,
+        update_voting_window,
+    },
+    export::{
+        current_results_csv, current_results_json, current_results_print, event_results_csv,
+        event_results_json, event_results_print,
+    },
     html,
-    page::page,
-    setlist::{add_song, clear_votes, delete_song, hide_song, setlist_page, unhide_song},
-    view::View,
-    vote::{delete_vote, vote_for_song, vote_songs},
-    vote_results::vote_result_page,
+    join::{join_event, join_page},
+    page::{branding, page, set_branding},
+    presenter::presenter_page,
+    qr::{current_qr_png, current_qr_svg, event_qr_png, event_qr_svg, qr_page},
+    rate_limit::{keep_saving_rejected_votes, limit_votes, RateLimiter, VoteGuard},
+    session::remember_me,
+    setlist::{
+        add_song, delete_song, hide_song, import_songs, preview_import, setlist_page, unhide_song,
+    },
+    updates::{sse_handler, websocket_handler, Update},
+    view::{escape, View},
+    vote::{current_vote_songs, delete_vote, vote_for_song, vote_song_list, vote_songs},
+    vote_results::{
+        current_vote_result_page, vote_result_page, vote_results_presenter, vote_results_table,
+    },
 };
 
 pub struct AppState {
-    rx: Receiver<Message>,
-    pub tx: Sender<Message>,
+    pub tx: Sender<Update>,
     pub database: Database,
+    pub key: Key,
+    pub vote_guard: VoteGuard,
+    /// Logins per IP address, kept apart from the votes so guests voting cannot lock out admins.
+    pub login_attempts: RateLimiter,
+    pub public_url: Option<String>,
+    /// The vote budget new events start with.
+    pub vote_budget: i32,
 }
 
-pub fn get_credentials() -> Credentials {
-    dotenv().ok();
+pub async fn serve_app(config: Config) -> anyhow::Result<()> {
+    let router = create_router(&config).await?;
+
+    let address = config.bind_address();
+    tracing::info!("listening on {}", address);
 
-    let hostname = env::var("DATABASE_HOSTNAME").unwrap();
-    let secret = env::var("DATABASE_SECRET").unwrap();
-    let user = env::var("DATABASE_USER").unwrap();
-    let database = env::var("DATABASE_NAME").unwrap();
-    let port = env::var("DATABASE_PORT").unwrap().parse::<i32>().unwrap();
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Could not listen on {}", address))?;
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
-    Credentials {
-        secret,
-        hostname,
-        user,
-        database,
-        port,
-    }
+    Ok(())
 }
 
-pub async fn serve_app() {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .with_target(false)
-        .compact()
-        .init();
+async fn create_router(config: &Config) -> anyhow::Result<Router> {
+    set_branding(config.branding.clone());
 
-    let router = create_router().await;
+    let database = Database::new(config.backend()).await?;
 
-    let address = "0.0.0.0:3000";
-    tracing::info!("listening on {}", address);
-
-    let listener = tokio::net::TcpListener::bind(address).await.unwrap();
-    axum::serve(listener, router).await.unwrap();
-}
+    if let Err(err) = bootstrap_admin(&database, &config.admin).await {
+        warn!("Failed to create admin: {}", err);
+    }
 
-async fn create_router() -> Router {
-    let credentials = get_credentials();
+    let (tx, _) = channel(64);
+    let shared_state = Arc::new(AppState {
+        tx,
+        database,
+        key: config.key(),
+        vote_guard: config.vote_guard(),
+        login_attempts: login_rate_limiter(),
+        public_url: config.public_url.clone(),
+        vote_budget: config.vote_budget,
+    });
 
-    let database = Database::new(credentials).await;
+    tokio::spawn(close_expired_voting(shared_state.clone()));
+    tokio::spawn(keep_saving_rejected_votes(shared_state.clone()));
 
-    let (tx, rx) = channel(Message::Text("{}".to_string()));
-    let shared_state = Arc::new(AppState { rx, tx, database });
+    Ok(app_router(shared_state))
+}
 
+/// All routes of the app, kept apart from reading the environment so tests can build
+/// the router around their own state.
+pub fn app_router(shared_state: Arc<AppState>) -> Router {
     let mut assets_path = std::env::current_dir().unwrap();
     assets_path.push("assets");
 
-    let router = axum::Router::new()
-        .route("/", get(index))
-        .route("/vote", get(vote_songs))
+    let admin_router = axum::Router::new()
         .route("/setlist", get(setlist_page).post(add_song))
+        .route("/setlist/import/preview", post(preview_import))
+        .route("/setlist/import", post(import_songs))
         .route("/setlist/:id", delete(delete_song))
         .route("/setlist/:id/hide", put(hide_song))
         .route("/setlist/:id/unhide", put(unhide_song))
-        .route("/setlist/votes/clear", post(clear_votes))
-        .route("/vote/results", get(vote_result_page))
-        .route("/vote/:song_id", post(vote_for_song).delete(delete_vote))
-        .route("/api/smoke", get(smoke_test))
-        .route("/websocket", get(websocket_handler))
-        .nest_service("/assets", ServeDir::new(assets_path.to_str().unwrap()))
-        .layer(
-            tower_http::trace::TraceLayer::new_for_http()
-                .make_span_with(trace::DefaultMakeSpan::new().level(tracing::Level::DEBUG))
-                .on_response(trace::DefaultOnResponse::new().level(tracing::Level::DEBUG)),
-        )
-        .layer(middleware::from_fn(remember_me));
+        .route("/setlist/events", get(events_page).post(add_event))
+        .route("/setlist/events/:id", get(event_page).delete(delete_event))
+        .route("/setlist/events/:id/songs", post(update_event_setlist))
+        .route("/setlist/events/:id/settings", post(update_event_settings))
+        .route("/setlist/events/:id/voting", post(update_voting_window))
+        .route("/setlist/events/:id/now-playing", post(update_now_playing))
+        .route("/setlist/events/:id/open", put(open_event))
+        .route("/setlist/events/:id/close", put(close_event))
+        .route("/setlist/events/:id/votes/clear", post(clear_votes))
+        .route("/setlist/events/:id/qr", get(qr_page))
+        .route("/setlist/events/:id/present", get(presenter_page))
+        .route("/setlist/events/:id/qr.svg", get(event_qr_svg))
+        .route("/setlist/events/:id/qr.png", get(event_qr_png))
+        .nest("/api/v1", api::admin_routes())
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            require_admin,
+        ));
+
+    // the pages where guests arrive, and the only ones that issue sessions
+    let guest_pages = axum::Router::new()
+        .route("/e/:slug/join", get(join_page).post(join_event))
+        .route("/e/:slug/vote", get(vote_songs))
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            remember_me,
+        ));
+
+    let router =
+        axum::Router::new()
+            .route("/", get(index))
+            .route("/login", get(login_page).post(login))
+            .route("/logout", post(logout))
+            .route("/vote", get(current_vote_songs))
+            .route("/vote/results", get(current_vote_result_page))
+            .route("/vote/results.csv", get(current_results_csv))
+            .route("/vote/results.json", get(current_results_json))
+            .route("/vote/results/print", get(current_results_print))
+            .route("/qr.svg", get(current_qr_svg))
+            .route("/qr.png", get(current_qr_png))
+            .merge(guest_pages)
+            .route("/e/:slug/songs", get(vote_song_list))
+            .route(
+                "/e/:slug/vote/:song_id",
+                post(vote_for_song).delete(delete_vote).route_layer(
+                    middleware::from_fn_with_state(shared_state.clone(), limit_votes),
+                ),
+            )
+            .route("/e/:slug/results", get(vote_result_page))
+            .route("/e/:slug/results.csv", get(event_results_csv))
+            .route("/e/:slug/results.json", get(event_results_json))
+            .route("/e/:slug/results/print", get(event_results_print))
+            .route("/e/:slug/results/table", get(vote_results_table))
+            .route("/e/:slug/results/presenter", get(vote_results_presenter))
+            .nest("/api/v1", api::public_routes(&shared_state))
+            .merge(admin_router)
+            .route("/api/smoke", get(smoke_test))
+            .route("/websocket", get(websocket_handler))
+            .route("/events", get(sse_handler))
+            .nest_service("/assets", ServeDir::new(assets_path.to_str().unwrap()))
+            .layer(
+                tower_http::trace::TraceLayer::new_for_http()
+                    .make_span_with(trace::DefaultMakeSpan::new().level(tracing::Level::DEBUG))
+                    .on_response(trace::DefaultOnResponse::new().level(tracing::Level::DEBUG)),
+            )
+            .layer(middleware::from_fn(error_pages));
 
     router.with_state(shared_state)
 }
 
-async fn index() -> View {
-    let index = html! {
-        <div class="flex flex-col gap-4">
-            <h1 class="text-lg">Stem på hvilke sange FestOrkestret skal spille</h1>
-
-            <a
-                class="p-2 text-lg text-center text-white bg-blue-500 rounded transition-colors hover:bg-blue-400"
-                href="/vote"
-            >
-                Stem her
-            </a>
-        </div>
+async fn index(State(state): State<Arc<AppState>>) -> Result<View, AppError> {
+    let index = match state.database.get_current_event().await? {
+        Some(event) => html! {
+            <div class="flex flex-col gap-4">
+                <h1 class="text-lg">{format!("Stem på hvilke sange {} skal spille", branding().name)}</h1>
+                <h2 class="text-neutral-500">{escape(&event.name)}</h2>
+
+                <a
+                    class="p-2 text-lg text-center text-white bg-blue-500 rounded transition-colors hover:bg-blue-400"
+                    href=format!("/e/{}/vote", event.slug)
+                >
+                    Stem her
+                </a>
+            </div>
+        },
+        None => html! {
+            <div class="flex flex-col gap-4">
+                <h1 class="text-lg">Der er ingen afstemning i gang lige nu</h1>
+            </div>
+        },
     };
 
-    page(index, "Festorkestret Setlist")
+    Ok(page(index, &format!("{} Setlist", branding().name)))
 }
 
 async fn smoke_test() -> impl IntoResponse {
     "Ok"
 }
-
-async fn websocket_handler(
-    ws: WebSocketUpgrade,
-    State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
-}
-
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
-    let (mut sender, _) = socket.split();
-
-    let mut rx = state.rx.clone();
-
-    tokio::spawn(async move {
-        while let Ok(()) = rx.changed().await {
-            let msg = rx.borrow().clone();
-
-            if sender.send(msg).await.is_err() {
-                info!("Client connection ended");
-                break;
-            }
-        }
-    });
-}
-
-async fn remember_me(mut jar: CookieJar, request: Request, next: Next) -> (CookieJar, Response) {
-    if jar.get("session_id").is_none() {
-        let mut cookie = Cookie::new("session_id", generate(6, charsets::ALPHA));
-        cookie.set_same_site(SameSite::Strict);
-        jar = jar.add(cookie);
-    }
-    (jar, next.run(request).await)
-}
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal},
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::{
+    admin::hash_password,
+    app::serve_app,
+    config::Config,
+    database::{Database, Event, NewSong},
+    import::{find_duplicates, parse_songs, Duplicate, ImportFormat},
+};
+
+/// Runs the setlist server, or manages a gig from the command line.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Runs the web server, the default when no command is given
+    Serve,
+    /// Brings the database schema up to date
+    Migrate,
+    /// Adds songs from a CSV, JSON or M3U file, or one with one `Artist - Title` per line
+    ImportSongs {
+        file: PathBuf,
+        /// Also puts the songs on the setlist of the event with this slug
+        #[arg(long)]
+        event: Option<String>,
+    },
+    /// Prints the results of an event
+    ExportResults {
+        /// The slug of the event
+        event: String,
+    },
+    /// Deletes every vote at an event
+    ClearVotes {
+        /// The slug of the event
+        event: String,
+        /// Confirms deleting the votes
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Creates an admin, prompting for the password or reading it from a pipe
+    CreateAdmin { username: String },
+    /// Lists the events, newest first
+    ListEvents,
+}
+
+pub async fn run() {
+    let cli = Cli::parse();
+
+    // logs go to stderr so they stay out of the output of the commands
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_writer(io::stderr)
+        .compact()
+        .init();
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprint!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = run_command(cli.command.unwrap_or(Command::Serve), config).await {
+        eprintln!("Error: {:#}", err);
+        process::exit(1);
+    }
+}
+
+async fn run_command(command: Command, config: Config) -> Result<()> {
+    match command {
+        Command::Serve => serve_app(config).await,
+        Command::Migrate => {
+            let database = Database::connect(config.backend()).await?;
+            database.migrate().await?;
+            println!("The database is up to date");
+            Ok(())
+        }
+        Command::ImportSongs { file, event } => {
+            let database = Database::new(config.backend()).await?;
+            import_songs(&database, &file, event.as_deref()).await
+        }
+        Command::ExportResults { event } => {
+            let database = Database::new(config.backend()).await?;
+            export_results(&database, &event).await
+        }
+        Command::ClearVotes { event, yes } => {
+            let database = Database::new(config.backend()).await?;
+            clear_votes(&database, &event, yes).await
+        }
+        Command::CreateAdmin { username } => {
+            let database = Database::new(config.backend()).await?;
+            create_admin(&database, &username).await
+        }
+        Command::ListEvents => {
+            let database = Database::new(config.backend()).await?;
+            list_events(&database).await
+        }
+    }
+}
+
+async fn event_by_slug(database: &Database, slug: &str) -> Result<Event> {
+    database
+        .get_event_by_slug(slug)
+        .await?
+        .ok_or_else(|| anyhow!("There is no event with the slug {}", slug))
+}
+
+async fn import_songs(database: &Database, file: &Path, slug: Option<&str>) -> Result<()> {
+    let contents =
+        fs::read_to_string(file).with_context(|| format!("Could not read {}", file.display()))?;
+
+    let format = ImportFormat::from_file_name(&file.to_string_lossy());
+    let songs = parse_songs(format, &contents)?;
+
+    let event = match slug {
+        Some(slug) => Some(event_by_slug(database, slug).await?),
+        None => None,
+    };
+
+    let repertoire = database.get_setlist().await?;
+    let (new_songs, duplicates): (Vec<_>, Vec<_>) = find_duplicates(songs, &repertoire)
+        .into_iter()
+        .partition(|row| row.duplicate.is_none());
+
+    for row in &duplicates {
+        let reason = match row.duplicate {
+            Some(Duplicate::Existing) => "it is already in the repertoire",
+            _ => "it is further up in the file",
+        };
+        println!(
+            "Skipping {} - {}, {}",
+            row.song.artist, row.song.title, reason
+        );
+    }
+
+    let new_songs: Vec<NewSong> = new_songs.into_iter().map(|row| row.song).collect();
+    let added = database.add_songs(&new_songs).await?;
+    println!("Imported {} songs", added.len());
+
+    if let Some(event) = event {
+        let mut song_ids: Vec<i32> = added.iter().map(|song| song.id).collect();
+        let setlist = database.get_event_setlist(event.id).await?;
+        song_ids.extend(setlist.iter().map(|song| song.id));
+        database.set_event_setlist(event.id, &song_ids).await?;
+        println!("Added them to the setlist of {}", event.name);
+    }
+
+    Ok(())
+}
+
+async fn export_results(database: &Database, slug: &str) -> Result<()> {
+    let event = event_by_slug(database, slug).await?;
+    let results = database.get_vote_results(&event).await?;
+    let voters = database.count_voters(event.id).await?;
+
+    println!("{} ({}), {} voters", event.name, event.date, voters);
+    println!("{:>3}  {:>6}  {:>5}  Song", "#", "Points", "Votes");
+    for (position, result) in results.iter().enumerate() {
+        println!(
+            "{:>3}  {:>6}  {:>5}  {} - {}",
+            position + 1,
+            result.points,
+            result.vote_count,
+            result.song.artist,
+            result.song.title
+        );
+    }
+
+    Ok(())
+}
+
+async fn clear_votes(database: &Database, slug: &str, yes: bool) -> Result<()> {
+    let event = event_by_slug(database, slug).await?;
+    let voters = database.count_voters(event.id).await?;
+
+    if !yes {
+        bail!(
+            "This deletes the votes of {} guests at {}, run again with --yes to go ahead",
+            voters,
+            event.name
+        );
+    }
+
+    database.clear_votes(event.id).await?;
+    println!("Deleted the votes of {} guests at {}", voters, event.name);
+
+    Ok(())
+}
+
+async fn create_admin(database: &Database, username: &str) -> Result<()> {
+    if database.get_admin_by_username(username).await?.is_some() {
+        bail!("The admin {} already exists", username);
+    }
+
+    // the password is not echoed at a terminal, while scripts can still pipe it in
+    let stdin = io::stdin();
+    let password = if stdin.is_terminal() {
+        rpassword::prompt_password(format!("Password for {}: ", username))?
+    } else {
+        let mut password = String::new();
+        stdin.lock().read_line(&mut password)?;
+        password.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if password.is_empty() {
+        bail!("The password cannot be empty");
+    }
+
+    database
+        .create_admin(username, &hash_password(&password)?)
+        .await?;
+    println!("Created the admin {}", username);
+
+    Ok(())
+}
+
+async fn list_events(database: &Database) -> Result<()> {
+    for event in database.get_events().await? {
+        let status = if event.accepts_votes() {
+            "voting"
+        } else if event.open {
+            "open, voting closed"
+        } else {
+            "closed"
+        };
+
+        println!(
+            "{:>4}  {}  {:<30}  {:<30}  {}",
+            event.id, event.date, event.slug, event.name, status
+        );
+    }
+
+    Ok(())
+}
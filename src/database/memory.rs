@@ -0,0 +1,643 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Mutex, MutexGuard},
+};
+
+use super::{
+    Admin, AdminSession, Event, NewSong, RejectedVotes, RejectionReason, Session, Song, Store,
+    Vote, VoteResult, VotingMode,
+};
+use crate::{
+    errors::{BadRequestError, StoreError, VoteError},
+    tally::tally,
+};
+
+/// Keeps everything in memory, for tests and for trying the app out. Every method holds the
+/// lock for its whole run, so each one is atomic like a database transaction.
+#[derive(Default)]
+pub struct MemoryStore {
+    data: Mutex<Data>,
+}
+
+#[derive(Default)]
+struct Data {
+    next_id: i32,
+    songs: BTreeMap<i32, Song>,
+    events: BTreeMap<i32, Event>,
+    event_songs: HashSet<(i32, i32)>,
+    votes: Vec<Vote>,
+    sessions: HashMap<String, Session>,
+    admissions: HashSet<(i32, String)>,
+    rejected_votes: Vec<(i32, RejectionReason, i32)>,
+    admins: BTreeMap<i32, Admin>,
+    admin_sessions: HashMap<String, AdminSession>,
+}
+
+impl Data {
+    fn next_id(&mut self) -> i32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn event_mut(&mut self, id: i32) -> Result<&mut Event> {
+        Ok(self.events.get_mut(&id).ok_or(StoreError::NotFound)?)
+    }
+
+    fn song_mut(&mut self, id: i32) -> Result<&mut Song> {
+        Ok(self.songs.get_mut(&id).ok_or(StoreError::NotFound)?)
+    }
+
+    fn event_setlist(&self, event_id: i32) -> Vec<Song> {
+        let mut songs: Vec<Song> = self
+            .songs
+            .values()
+            .filter(|song| self.event_songs.contains(&(event_id, song.id)))
+            .cloned()
+            .collect();
+        songs.sort_by(|a, b| a.artist.cmp(&b.artist));
+        songs
+    }
+
+    fn session_votes<'a>(
+        &'a self,
+        event_id: i32,
+        session_id: &'a str,
+    ) -> impl Iterator<Item = &'a Vote> {
+        self.votes
+            .iter()
+            .filter(move |vote| vote.event_id == event_id && vote.session_id == session_id)
+    }
+}
+
+impl MemoryStore {
+    fn data(&self) -> MutexGuard<'_, Data> {
+        // the data stays consistent even if a panic poisoned the lock
+        self.data.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Events sorted like `order by date desc, id desc`.
+fn newest_first(events: impl Iterator<Item = Event>) -> Vec<Event> {
+    let mut events: Vec<Event> = events.collect();
+    events.sort_by(|a, b| b.date.cmp(&a.date).then(b.id.cmp(&a.id)));
+    events
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn migrate(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_setlist(&self) -> Result<Vec<Song>> {
+        let mut songs: Vec<Song> = self.data().songs.values().cloned().collect();
+        songs.sort_by(|a, b| a.artist.cmp(&b.artist));
+
+        Ok(songs)
+    }
+
+    async fn get_event_setlist(&self, event_id: i32) -> Result<Vec<Song>> {
+        Ok(self.data().event_setlist(event_id))
+    }
+
+    async fn set_event_setlist(&self, event_id: i32, song_ids: &[i32]) -> Result<()> {
+        let mut data = self.data();
+
+        // all songs are checked first, so an unknown one leaves the setlist as it was
+        if !song_ids
+            .iter()
+            .all(|song_id| data.songs.contains_key(song_id))
+        {
+            return Err(StoreError::NotFound.into());
+        }
+
+        data.event_songs.retain(|(event, _)| *event != event_id);
+        for song_id in song_ids {
+            data.event_songs.insert((event_id, *song_id));
+        }
+
+        Ok(())
+    }
+
+    async fn get_events(&self) -> Result<Vec<Event>> {
+        Ok(newest_first(self.data().events.values().cloned()))
+    }
+
+    async fn get_event(&self, id: i32) -> Result<Option<Event>> {
+        Ok(self.data().events.get(&id).cloned())
+    }
+
+    async fn get_event_by_slug(&self, slug: &str) -> Result<Option<Event>> {
+        let data = self.data();
+        let event = data.events.values().find(|event| event.slug == slug);
+
+        Ok(event.cloned())
+    }
+
+    async fn get_current_event(&self) -> Result<Option<Event>> {
+        let data = self.data();
+        let open = data.events.values().filter(|event| event.open).cloned();
+
+        Ok(newest_first(open).into_iter().next())
+    }
+
+    async fn get_song_events(&self, song_id: i32) -> Result<Vec<Event>> {
+        let data = self.data();
+        let events = data
+            .events
+            .values()
+            .filter(|event| data.event_songs.contains(&(event.id, song_id)))
+            .cloned()
+            .collect();
+
+        Ok(events)
+    }
+
+    async fn create_event(
+        &self,
+        slug: &str,
+        name: &str,
+        date: NaiveDate,
+        venue: Option<&str>,
+        vote_budget: i32,
+    ) -> Result<Event> {
+        let mut data = self.data();
+
+        if data.events.values().any(|event| event.slug == slug) {
+            return Err(StoreError::Duplicate.into());
+        }
+
+        let event = Event {
+            id: data.next_id(),
+            slug: slug.to_string(),
+            name: name.to_string(),
+            date,
+            venue: venue.map(str::to_string),
+            open: true,
+            vote_budget,
+            voting_mode: VotingMode::Approval,
+            voting_open: true,
+            closes_at: None,
+            max_sessions_per_ip: None,
+            join_code: None,
+            now_playing: None,
+        };
+        data.events.insert(event.id, event.clone());
+
+        Ok(event)
+    }
+
+    async fn set_event_open(&self, id: i32, open: bool) -> Result<Event> {
+        let mut data = self.data();
+        let event = data.event_mut(id)?;
+        event.open = open;
+
+        Ok(event.clone())
+    }
+
+    async fn update_event_settings(
+        &self,
+        id: i32,
+        vote_budget: i32,
+        voting_mode: VotingMode,
+        max_sessions_per_ip: Option<i32>,
+        join_code: Option<&str>,
+    ) -> Result<Event> {
+        let mut data = self.data();
+        let event = data.event_mut(id)?;
+        event.vote_budget = vote_budget;
+        event.voting_mode = voting_mode;
+        event.max_sessions_per_ip = max_sessions_per_ip;
+        event.join_code = join_code.map(str::to_string);
+
+        Ok(event.clone())
+    }
+
+    async fn set_voting_window(
+        &self,
+        id: i32,
+        voting_open: bool,
+        closes_at: Option<DateTime<Utc>>,
+    ) -> Result<Event> {
+        let mut data = self.data();
+        let event = data.event_mut(id)?;
+        event.voting_open = voting_open;
+        event.closes_at = closes_at;
+
+        Ok(event.clone())
+    }
+
+    async fn set_now_playing(&self, id: i32, song_id: Option<i32>) -> Result<Event> {
+        let mut data = self.data();
+
+        if song_id.is_some_and(|song_id| !data.songs.contains_key(&song_id)) {
+            return Err(StoreError::NotFound.into());
+        }
+
+        let event = data.event_mut(id)?;
+        event.now_playing = song_id;
+
+        Ok(event.clone())
+    }
+
+    async fn close_expired_voting(&self) -> Result<Vec<Event>> {
+        let mut data = self.data();
+
+        let closed = data
+            .events
+            .values_mut()
+            .filter(|event| event.voting_open && event.closing_time_passed())
+            .map(|event| {
+                event.voting_open = false;
+                event.clone()
+            })
+            .collect();
+
+        Ok(closed)
+    }
+
+    async fn delete_event(&self, id: i32) -> Result<()> {
+        let mut data = self.data();
+
+        data.events.remove(&id);
+        data.event_songs.retain(|(event_id, _)| *event_id != id);
+        data.votes.retain(|vote| vote.event_id != id);
+        data.admissions.retain(|(event_id, _)| *event_id != id);
+        data.rejected_votes
+            .retain(|(event_id, _, _)| *event_id != id);
+
+        Ok(())
+    }
+
+    async fn get_vote_results(&self, event: &Event) -> Result<Vec<VoteResult>> {
+        let data = self.data();
+
+        let songs = data
+            .event_setlist(event.id)
+            .into_iter()
+            .filter(|song| !song.hidden)
+            .collect();
+        let votes: Vec<Vote> = data
+            .votes
+            .iter()
+            .filter(|vote| vote.event_id == event.id)
+            .cloned()
+            .collect();
+
+        Ok(tally(event, songs, &votes))
+    }
+
+    async fn get_votes(&self, event_id: i32, session_id: &str) -> Result<Vec<Vote>> {
+        let data = self.data();
+        let votes = data.session_votes(event_id, session_id).cloned().collect();
+
+        Ok(votes)
+    }
+
+    async fn count_votes(&self, event_id: i32, session_id: &str) -> Result<i64> {
+        let data = self.data();
+        let count = data.session_votes(event_id, session_id).count();
+
+        Ok(i64::try_from(count)?)
+    }
+
+    async fn count_voters(&self, event_id: i32) -> Result<i64> {
+        let data = self.data();
+        let voters: HashSet<&str> = data
+            .votes
+            .iter()
+            .filter(|vote| vote.event_id == event_id)
+            .map(|vote| vote.session_id.as_str())
+            .collect();
+
+        Ok(i64::try_from(voters.len())?)
+    }
+
+    async fn count_ip_sessions(&self, event_id: i32, ip: &str, session_id: &str) -> Result<i64> {
+        let data = self.data();
+        let sessions: HashSet<&str> = data
+            .votes
+            .iter()
+            .filter(|vote| vote.event_id == event_id && vote.session_id != session_id)
+            .filter(|vote| {
+                data.sessions
+                    .get(&vote.session_id)
+                    .is_some_and(|session| session.ip.as_deref() == Some(ip))
+            })
+            .map(|vote| vote.session_id.as_str())
+            .collect();
+
+        Ok(i64::try_from(sessions.len())?)
+    }
+
+    async fn record_rejected_vote(
+        &self,
+        event_id: i32,
+        _session_id: &str,
+        _ip: Option<&str>,
+        reason: RejectionReason,
+        count: i32,
+    ) -> Result<()> {
+        let mut data = self.data();
+
+        if !data.events.contains_key(&event_id) {
+            return Err(StoreError::NotFound.into());
+        }
+        data.rejected_votes.push((event_id, reason, count));
+
+        Ok(())
+    }
+
+    async fn get_rejected_votes(&self, event_id: i32) -> Result<Vec<RejectedVotes>> {
+        let data = self.data();
+
+        let mut counts: BTreeMap<&str, RejectedVotes> = BTreeMap::new();
+        for (_, reason, count) in data
+            .rejected_votes
+            .iter()
+            .filter(|(event, _, _)| *event == event_id)
+        {
+            counts
+                .entry(reason.as_str())
+                .or_insert(RejectedVotes {
+                    reason: *reason,
+                    count: 0,
+                })
+                .count += i64::from(*count);
+        }
+
+        Ok(counts.into_values().collect())
+    }
+
+    async fn create_vote(
+        &self,
+        event_id: i32,
+        session_id: &str,
+        song_id: i32,
+        vote_budget: i32,
+    ) -> Result<(Song, i32)> {
+        let mut data = self.data();
+
+        let Some(song) = data.songs.get(&song_id).cloned() else {
+            return Err(VoteError::UnknownSong.into());
+        };
+        if data
+            .session_votes(event_id, session_id)
+            .any(|vote| vote.song_id == song_id)
+        {
+            return Err(VoteError::AlreadyVoted.into());
+        }
+        if !data.event_songs.contains(&(event_id, song_id)) {
+            return Err(VoteError::NotOnSetlist.into());
+        }
+        if song.hidden {
+            return Err(VoteError::HiddenSong.into());
+        }
+        let vote_count = data.session_votes(event_id, session_id).count();
+        if vote_count >= usize::try_from(vote_budget).unwrap_or(0) {
+            return Err(BadRequestError::TooManyVotes.into());
+        }
+        if !data.sessions.contains_key(session_id) {
+            return Err(StoreError::NotFound.into());
+        }
+
+        let rank = i32::try_from(vote_count + 1)?;
+        let id = data.next_id();
+        data.votes.push(Vote {
+            id,
+            session_id: session_id.to_string(),
+            song_id,
+            event_id,
+            rank,
+        });
+
+        Ok((song, rank))
+    }
+
+    async fn delete_vote(&self, event_id: i32, session_id: &str, song_id: i32) -> Result<Song> {
+        let mut data = self.data();
+
+        let Some(song) = data.songs.get(&song_id).cloned() else {
+            return Err(VoteError::UnknownSong.into());
+        };
+
+        let Some(position) = data.votes.iter().position(|vote| {
+            vote.event_id == event_id && vote.session_id == session_id && vote.song_id == song_id
+        }) else {
+            return Err(VoteError::NotVoted.into());
+        };
+
+        let deleted = data.votes.remove(position);
+        for vote in data.votes.iter_mut() {
+            if vote.event_id == event_id
+                && vote.session_id == session_id
+                && vote.rank > deleted.rank
+            {
+                vote.rank -= 1;
+            }
+        }
+
+        Ok(song)
+    }
+
+    async fn clear_votes(&self, event_id: i32) -> Result<()> {
+        self.data().votes.retain(|vote| vote.event_id != event_id);
+
+        Ok(())
+    }
+
+    async fn add_song(&self, artist: &str, title: &str, description: Option<&str>) -> Result<Song> {
+        let mut data = self.data();
+
+        let song = Song {
+            id: data.next_id(),
+            artist: artist.to_string(),
+            title: title.to_string(),
+            description: description.map(str::to_string),
+            hidden: false,
+        };
+        data.songs.insert(song.id, song.clone());
+
+        Ok(song)
+    }
+
+    async fn add_songs(&self, songs: &[NewSong]) -> Result<Vec<Song>> {
+        let mut data = self.data();
+
+        let mut result = Vec::new();
+        for song in songs {
+            let song = Song {
+                id: data.next_id(),
+                artist: song.artist.clone(),
+                title: song.title.clone(),
+                description: song.description.clone(),
+                hidden: false,
+            };
+            data.songs.insert(song.id, song.clone());
+            result.push(song);
+        }
+
+        Ok(result)
+    }
+
+    async fn delete_song(&self, id: i32) -> Result<()> {
+        let mut data = self.data();
+
+        data.songs.remove(&id);
+        data.event_songs.retain(|(_, song_id)| *song_id != id);
+        data.votes.retain(|vote| vote.song_id != id);
+        for event in data.events.values_mut() {
+            if event.now_playing == Some(id) {
+                event.now_playing = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn hide_song(&self, id: i32) -> Result<Song> {
+        let mut data = self.data();
+        let song = data.song_mut(id)?;
+        song.hidden = true;
+
+        Ok(song.clone())
+    }
+
+    async fn unhide_song(&self, id: i32) -> Result<Song> {
+        let mut data = self.data();
+        let song = data.song_mut(id)?;
+        song.hidden = false;
+
+        Ok(song.clone())
+    }
+
+    async fn get_song(&self, id: i32) -> Result<Option<Song>> {
+        Ok(self.data().songs.get(&id).cloned())
+    }
+
+    async fn update_song(
+        &self,
+        id: i32,
+        artist: &str,
+        title: &str,
+        description: Option<&str>,
+    ) -> Result<Song> {
+        let mut data = self.data();
+        let song = data.song_mut(id)?;
+        song.artist = artist.to_string();
+        song.title = title.to_string();
+        song.description = description.map(str::to_string);
+
+        Ok(song.clone())
+    }
+
+    async fn create_session(&self, id: &str, ip: Option<&str>) -> Result<Session> {
+        let mut data = self.data();
+
+        if data.sessions.contains_key(id) {
+            return Err(StoreError::Duplicate.into());
+        }
+
+        let session = Session {
+            id: id.to_string(),
+            created_at: Utc::now(),
+            ip: ip.map(str::to_string),
+        };
+        data.sessions.insert(session.id.clone(), session.clone());
+
+        Ok(session)
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>> {
+        Ok(self.data().sessions.get(id).cloned())
+    }
+
+    async fn admit_session(&self, event_id: i32, session_id: &str) -> Result<()> {
+        let mut data = self.data();
+
+        if !data.events.contains_key(&event_id) || !data.sessions.contains_key(session_id) {
+            return Err(StoreError::NotFound.into());
+        }
+        data.admissions.insert((event_id, session_id.to_string()));
+
+        Ok(())
+    }
+
+    async fn is_admitted(&self, event_id: i32, session_id: &str) -> Result<bool> {
+        let data = self.data();
+
+        Ok(data
+            .admissions
+            .contains(&(event_id, session_id.to_string())))
+    }
+
+    async fn create_admin(&self, username: &str, password_hash: &str) -> Result<Admin> {
+        let mut data = self.data();
+
+        if data.admins.values().any(|admin| admin.username == username) {
+            return Err(StoreError::Duplicate.into());
+        }
+
+        let admin = Admin {
+            id: data.next_id(),
+            username: username.to_string(),
+            password_hash: password_hash.to_string(),
+        };
+        data.admins.insert(admin.id, admin.clone());
+
+        Ok(admin)
+    }
+
+    async fn get_admin(&self, id: i32) -> Result<Option<Admin>> {
+        Ok(self.data().admins.get(&id).cloned())
+    }
+
+    async fn get_admin_by_username(&self, username: &str) -> Result<Option<Admin>> {
+        let data = self.data();
+        let admin = data
+            .admins
+            .values()
+            .find(|admin| admin.username == username);
+
+        Ok(admin.cloned())
+    }
+
+    async fn create_admin_session(
+        &self,
+        id: &str,
+        admin_id: i32,
+        expires_at: DateTime<Utc>,
+    ) -> Result<AdminSession> {
+        let mut data = self.data();
+
+        if !data.admins.contains_key(&admin_id) {
+            return Err(StoreError::NotFound.into());
+        }
+        if data.admin_sessions.contains_key(id) {
+            return Err(StoreError::Duplicate.into());
+        }
+
+        let session = AdminSession {
+            id: id.to_string(),
+            admin_id,
+            expires_at,
+        };
+        data.admin_sessions
+            .insert(session.id.clone(), session.clone());
+
+        Ok(session)
+    }
+
+    async fn get_admin_session(&self, id: &str) -> Result<Option<AdminSession>> {
+        Ok(self.data().admin_sessions.get(id).cloned())
+    }
+
+    async fn delete_admin_session(&self, id: &str) -> Result<()> {
+        self.data().admin_sessions.remove(id);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,473 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{ops::Deref, sync::Arc};
+use tracing::{info, warn};
+
+mod memory;
+mod postgres;
+mod sqlite;
+
+pub use memory::MemoryStore;
+pub use postgres::PostgresStore;
+pub use sqlite::SqliteStore;
+
+/// Where the app keeps its data.
+#[derive(Clone)]
+pub enum Backend {
+    Postgres {
+        url: String,
+        pool_size: u32,
+    },
+    /// A single SQLite file given by its `sqlite://` URL, for small gigs run from a laptop.
+    Sqlite(String),
+    /// Nothing is saved, everything is gone on restart.
+    Memory,
+}
+
+/// Everything the app stores, whichever backend it is kept in.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Brings the schema up to date.
+    async fn migrate(&self) -> Result<()>;
+
+    async fn get_setlist(&self) -> Result<Vec<Song>>;
+
+    async fn get_event_setlist(&self, event_id: i32) -> Result<Vec<Song>>;
+
+    /// Replaces the songs guests can vote for at an event with `song_ids`.
+    async fn set_event_setlist(&self, event_id: i32, song_ids: &[i32]) -> Result<()>;
+
+    async fn get_events(&self) -> Result<Vec<Event>>;
+
+    async fn get_event(&self, id: i32) -> Result<Option<Event>>;
+
+    async fn get_event_by_slug(&self, slug: &str) -> Result<Option<Event>>;
+
+    /// The most recent open event, which is the one guests are sent to from `/vote`.
+    async fn get_current_event(&self) -> Result<Option<Event>>;
+
+    /// The events with the song on their setlist.
+    async fn get_song_events(&self, song_id: i32) -> Result<Vec<Event>>;
+
+    async fn create_event(
+        &self,
+        slug: &str,
+        name: &str,
+        date: NaiveDate,
+        venue: Option<&str>,
+        vote_budget: i32,
+    ) -> Result<Event>;
+
+    async fn set_event_open(&self, id: i32, open: bool) -> Result<Event>;
+
+    async fn update_event_settings(
+        &self,
+        id: i32,
+        vote_budget: i32,
+        voting_mode: VotingMode,
+        max_sessions_per_ip: Option<i32>,
+        join_code: Option<&str>,
+    ) -> Result<Event>;
+
+    async fn set_voting_window(
+        &self,
+        id: i32,
+        voting_open: bool,
+        closes_at: Option<DateTime<Utc>>,
+    ) -> Result<Event>;
+
+    async fn set_now_playing(&self, id: i32, song_id: Option<i32>) -> Result<Event>;
+
+    /// Closes voting for events whose scheduled closing time has passed.
+    async fn close_expired_voting(&self) -> Result<Vec<Event>>;
+
+    async fn delete_event(&self, id: i32) -> Result<()>;
+
+    /// The tallied votes for the visible songs on the setlist of an event.
+    async fn get_vote_results(&self, event: &Event) -> Result<Vec<VoteResult>>;
+
+    async fn get_votes(&self, event_id: i32, session_id: &str) -> Result<Vec<Vote>>;
+
+    async fn count_votes(&self, event_id: i32, session_id: &str) -> Result<i64>;
+
+    /// Counts the guests that have at least one vote at an event.
+    async fn count_voters(&self, event_id: i32) -> Result<i64>;
+
+    /// Counts the other sessions from the same IP address that have voted at an event.
+    async fn count_ip_sessions(&self, event_id: i32, ip: &str, session_id: &str) -> Result<i64>;
+
+    /// Saves `count` votes by a session that were turned away for the same reason.
+    async fn record_rejected_vote(
+        &self,
+        event_id: i32,
+        session_id: &str,
+        ip: Option<&str>,
+        reason: RejectionReason,
+        count: i32,
+    ) -> Result<()>;
+
+    async fn get_rejected_votes(&self, event_id: i32) -> Result<Vec<RejectedVotes>>;
+
+    /// Votes for a song, returning the song and the rank of the vote. The session's votes
+    /// are counted and inserted atomically, so concurrent votes cannot exceed
+    /// `vote_budget`. Fails with a `VoteError` when the song cannot be voted for.
+    async fn create_vote(
+        &self,
+        event_id: i32,
+        session_id: &str,
+        song_id: i32,
+        vote_budget: i32,
+    ) -> Result<(Song, i32)>;
+
+    /// Deletes a vote and moves the session's later votes up a rank, atomically like
+    /// `create_vote`.
+    async fn delete_vote(&self, event_id: i32, session_id: &str, song_id: i32) -> Result<Song>;
+
+    async fn clear_votes(&self, event_id: i32) -> Result<()>;
+
+    async fn add_song(&self, artist: &str, title: &str, description: Option<&str>) -> Result<Song>;
+
+    /// Adds all of `songs` in one transaction, so a failed import adds none of them.
+    async fn add_songs(&self, songs: &[NewSong]) -> Result<Vec<Song>>;
+
+    async fn delete_song(&self, id: i32) -> Result<()>;
+
+    async fn hide_song(&self, id: i32) -> Result<Song>;
+
+    async fn unhide_song(&self, id: i32) -> Result<Song>;
+
+    async fn get_song(&self, id: i32) -> Result<Option<Song>>;
+
+    async fn update_song(
+        &self,
+        id: i32,
+        artist: &str,
+        title: &str,
+        description: Option<&str>,
+    ) -> Result<Song>;
+
+    async fn create_session(&self, id: &str, ip: Option<&str>) -> Result<Session>;
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>>;
+
+    async fn admit_session(&self, event_id: i32, session_id: &str) -> Result<()>;
+
+    async fn is_admitted(&self, event_id: i32, session_id: &str) -> Result<bool>;
+
+    async fn create_admin(&self, username: &str, password_hash: &str) -> Result<Admin>;
+
+    async fn get_admin(&self, id: i32) -> Result<Option<Admin>>;
+
+    async fn get_admin_by_username(&self, username: &str) -> Result<Option<Admin>>;
+
+    async fn create_admin_session(
+        &self,
+        id: &str,
+        admin_id: i32,
+        expires_at: DateTime<Utc>,
+    ) -> Result<AdminSession>;
+
+    async fn get_admin_session(&self, id: &str) -> Result<Option<AdminSession>>;
+
+    /// Deletes an admin session, so its cookie no longer signs anyone in.
+    async fn delete_admin_session(&self, id: &str) -> Result<()>;
+}
+
+/// The store the app was configured with. Dereferences to the `Store` so handlers call its
+/// methods directly.
+#[derive(Clone)]
+pub struct Database {
+    store: Arc<dyn Store>,
+}
+
+impl Database {
+    /// Connects to the backend and migrates it, carrying on if the migrations fail.
+    pub async fn new(backend: Backend) -> Result<Database> {
+        let database = Database::connect(backend).await?;
+
+        match database.migrate().await {
+            Ok(_) => info!("Migrated database"),
+            Err(err) => warn!("Error migrating database: {}", err),
+        };
+
+        Ok(database)
+    }
+
+    /// Connects to the backend without touching its schema.
+    pub async fn connect(backend: Backend) -> Result<Database> {
+        Ok(match backend {
+            Backend::Postgres { url, pool_size } => {
+                Database::from_store(PostgresStore::connect(&url, pool_size).await?)
+            }
+            Backend::Sqlite(url) => Database::from_store(SqliteStore::connect(&url).await?),
+            Backend::Memory => Database::from_store(MemoryStore::default()),
+        })
+    }
+
+    pub fn new_from_pool(pool: PgPool) -> Database {
+        Database::from_store(PostgresStore::new_from_pool(pool))
+    }
+
+    pub fn from_store(store: impl Store + 'static) -> Database {
+        Database {
+            store: Arc::new(store),
+        }
+    }
+}
+
+impl Deref for Database {
+    type Target = dyn Store;
+
+    fn deref(&self) -> &Self::Target {
+        self.store.as_ref()
+    }
+}
+
+#[derive(sqlx::FromRow, Serialize, Clone, Debug)]
+pub struct Song {
+    pub id: i32,
+    pub artist: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub hidden: bool,
+}
+
+/// A song about to be added to the repertoire.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NewSong {
+    pub artist: String,
+    pub title: String,
+    pub description: Option<String>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Clone)]
+pub struct Vote {
+    pub id: i32,
+    pub session_id: String,
+    pub song_id: i32,
+    pub event_id: i32,
+    pub rank: i32,
+}
+
+#[derive(sqlx::FromRow, Serialize, Clone)]
+pub struct Event {
+    pub id: i32,
+    pub slug: String,
+    pub name: String,
+    pub date: NaiveDate,
+    pub venue: Option<String>,
+    pub open: bool,
+    pub vote_budget: i32,
+    #[sqlx(try_from = "String")]
+    pub voting_mode: VotingMode,
+    pub voting_open: bool,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub max_sessions_per_ip: Option<i32>,
+    #[serde(skip)]
+    pub join_code: Option<String>,
+    pub now_playing: Option<i32>,
+}
+
+impl Event {
+    /// Guests can vote while the event is open, voting is open and the closing time, if
+    /// any, has not passed.
+    pub fn accepts_votes(&self) -> bool {
+        self.open && self.voting_open && !self.closing_time_passed()
+    }
+
+    pub fn closing_time_passed(&self) -> bool {
+        self.closes_at
+            .is_some_and(|closes_at| Utc::now() >= closes_at)
+    }
+
+    /// Join codes are compared ignoring case and surrounding whitespace, since guests type
+    /// them in on their phones.
+    pub fn join_code_matches(&self, code: &str) -> bool {
+        self.join_code
+            .as_deref()
+            .is_none_or(|join_code| join_code.trim().eq_ignore_ascii_case(code.trim()))
+    }
+
+    /// An open event accepting approval votes, for tests to change what they need.
+    #[cfg(test)]
+    pub fn for_tests() -> Event {
+        Event {
+            id: 1,
+            slug: "koncert".to_string(),
+            name: "Koncert".to_string(),
+            date: NaiveDate::from_ymd_opt(2030, 6, 1).unwrap(),
+            venue: None,
+            open: true,
+            vote_budget: 5,
+            voting_mode: VotingMode::Approval,
+            voting_open: true,
+            closes_at: None,
+            max_sessions_per_ip: None,
+            join_code: None,
+            now_playing: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VotingMode {
+    /// Every vote counts as one.
+    Approval,
+    /// Votes are worth more the earlier the guest picked the song.
+    Borda,
+    /// Votes transfer to the guest's next pick when their song is eliminated.
+    InstantRunoff,
+}
+
+impl VotingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VotingMode::Approval => "approval",
+            VotingMode::Borda => "borda",
+            VotingMode::InstantRunoff => "instant_runoff",
+        }
+    }
+
+    pub fn is_ranked(&self) -> bool {
+        *self != VotingMode::Approval
+    }
+}
+
+impl From<String> for VotingMode {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "borda" => VotingMode::Borda,
+            "instant_runoff" => VotingMode::InstantRunoff,
+            _ => VotingMode::Approval,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct Session {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub ip: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum RejectionReason {
+    /// Too many votes from one IP address within the rate limit window.
+    IpRateLimit,
+    /// Too many votes from one session within the rate limit window.
+    SessionRateLimit,
+    /// The IP address already has the maximum number of voting sessions at the event.
+    SessionsPerIp,
+}
+
+impl RejectionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RejectionReason::IpRateLimit => "ip_rate_limit",
+            RejectionReason::SessionRateLimit => "session_rate_limit",
+            RejectionReason::SessionsPerIp => "sessions_per_ip",
+        }
+    }
+}
+
+impl From<String> for RejectionReason {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "ip_rate_limit" => RejectionReason::IpRateLimit,
+            "session_rate_limit" => RejectionReason::SessionRateLimit,
+            _ => RejectionReason::SessionsPerIp,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct RejectedVotes {
+    #[sqlx(try_from = "String")]
+    pub reason: RejectionReason,
+    pub count: i64,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct Admin {
+    pub id: i32,
+    pub username: String,
+    pub password_hash: String,
+}
+
+/// An admin's sign-in, kept on the server so logging out revokes it.
+#[derive(sqlx::FromRow, Clone)]
+pub struct AdminSession {
+    pub id: String,
+    pub admin_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AdminSession {
+    pub fn has_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct VoteResult {
+    pub song: Song,
+    pub vote_count: i64,
+    pub points: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn votes_need_an_open_event_with_open_voting() {
+        assert!(Event::for_tests().accepts_votes());
+
+        let closed = Event {
+            open: false,
+            ..Event::for_tests()
+        };
+        assert!(!closed.accepts_votes());
+
+        let voting_closed = Event {
+            voting_open: false,
+            ..Event::for_tests()
+        };
+        assert!(!voting_closed.accepts_votes());
+    }
+
+    #[test]
+    fn votes_stop_at_the_closing_time() {
+        let closing_later = Event {
+            closes_at: Some(Utc::now() + Duration::minutes(5)),
+            ..Event::for_tests()
+        };
+        assert!(closing_later.accepts_votes());
+        assert!(!closing_later.closing_time_passed());
+
+        let closed_earlier = Event {
+            closes_at: Some(Utc::now() - Duration::minutes(5)),
+            ..Event::for_tests()
+        };
+        assert!(!closed_earlier.accepts_votes());
+        assert!(closed_earlier.closing_time_passed());
+    }
+
+    #[test]
+    fn join_codes_ignore_case_and_surrounding_spaces() {
+        assert!(Event::for_tests().join_code_matches("hvad som helst"));
+
+        let with_code = Event {
+            join_code: Some("SCENE".to_string()),
+            ..Event::for_tests()
+        };
+        assert!(with_code.join_code_matches(" scene "));
+        assert!(!with_code.join_code_matches("scener"));
+        assert!(!with_code.join_code_matches(""));
+    }
+}
@@ -0,0 +1,678 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+use super::{
+    Admin, AdminSession, Event, NewSong, RejectedVotes, RejectionReason, Session, Song, Store,
+    Vote, VoteResult, VotingMode,
+};
+use crate::{
+    errors::{BadRequestError, VoteError},
+    tally::tally,
+};
+
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    pub async fn connect(url: &str, pool_size: u32) -> Result<PostgresStore> {
+        let pool = PgPoolOptions::new()
+            .max_connections(pool_size)
+            .connect(url)
+            .await
+            .context("Could not connect to Postgres")?;
+
+        Ok(PostgresStore { pool })
+    }
+
+    pub fn new_from_pool(pool: PgPool) -> PostgresStore {
+        PostgresStore { pool }
+    }
+
+    /// Finds out why a vote for a song was not created.
+    async fn vote_rejection(
+        &self,
+        event_id: i32,
+        session_id: &str,
+        song_id: i32,
+    ) -> Result<VoteError> {
+        let song = sqlx::query!(
+            r#"select s.hidden, exists (select 1 from event_songs where event_id = $1 and song_id = s.id) as "on_setlist!", exists (select 1 from votes where event_id = $1 and session_id = $2 and song_id = s.id) as "voted!" from songs s where s.id = $3"#,
+            event_id,
+            session_id,
+            song_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match song {
+            None => VoteError::UnknownSong,
+            Some(song) if song.voted => VoteError::AlreadyVoted,
+            Some(song) if !song.on_setlist => VoteError::NotOnSetlist,
+            Some(song) if song.hidden => VoteError::HiddenSong,
+            // the vote was deleted again before we looked
+            Some(_) => VoteError::NotVoted,
+        })
+    }
+}
+
+#[async_trait]
+impl Store for PostgresStore {
+    async fn migrate(&self) -> Result<()> {
+        sqlx::migrate!("./migrations").run(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn get_setlist(&self) -> Result<Vec<Song>> {
+        let result = sqlx::query_as!(Song, "select * from songs order by artist")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_event_setlist(&self, event_id: i32) -> Result<Vec<Song>> {
+        let result = sqlx::query_as!(
+            Song,
+            "select s.* from songs s join event_songs es on s.id = es.song_id where es.event_id = $1 order by s.artist",
+            event_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_event_setlist(&self, event_id: i32, song_ids: &[i32]) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query!(
+            "delete from event_songs where event_id = $1 and not song_id = any($2)",
+            event_id,
+            song_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "insert into event_songs (event_id, song_id) select $1, unnest($2::int[]) on conflict do nothing",
+            event_id,
+            song_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_events(&self) -> Result<Vec<Event>> {
+        let result = sqlx::query_as!(Event, "select * from events order by date desc, id desc")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_event(&self, id: i32) -> Result<Option<Event>> {
+        let result = sqlx::query_as!(Event, "select * from events where id = $1", id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_event_by_slug(&self, slug: &str) -> Result<Option<Event>> {
+        let result = sqlx::query_as!(Event, "select * from events where slug = $1", slug)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_current_event(&self) -> Result<Option<Event>> {
+        let result = sqlx::query_as!(
+            Event,
+            "select * from events where open order by date desc, id desc limit 1"
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn get_song_events(&self, song_id: i32) -> Result<Vec<Event>> {
+        let result = sqlx::query_as!(
+            Event,
+            "select e.* from events e join event_songs es on e.id = es.event_id where es.song_id = $1",
+            song_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_event(
+        &self,
+        slug: &str,
+        name: &str,
+        date: NaiveDate,
+        venue: Option<&str>,
+        vote_budget: i32,
+    ) -> Result<Event> {
+        let result = sqlx::query_as!(
+            Event,
+            "insert into events (slug, name, date, venue, vote_budget) values ($1, $2, $3, $4, $5) returning *",
+            slug,
+            name,
+            date,
+            venue,
+            vote_budget
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_event_open(&self, id: i32, open: bool) -> Result<Event> {
+        let result = sqlx::query_as!(
+            Event,
+            "update events set open = $2 where id = $1 returning *",
+            id,
+            open
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn update_event_settings(
+        &self,
+        id: i32,
+        vote_budget: i32,
+        voting_mode: VotingMode,
+        max_sessions_per_ip: Option<i32>,
+        join_code: Option<&str>,
+    ) -> Result<Event> {
+        let result = sqlx::query_as!(
+            Event,
+            "update events set vote_budget = $2, voting_mode = $3, max_sessions_per_ip = $4, join_code = $5 where id = $1 returning *",
+            id,
+            vote_budget,
+            voting_mode.as_str(),
+            max_sessions_per_ip,
+            join_code
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_voting_window(
+        &self,
+        id: i32,
+        voting_open: bool,
+        closes_at: Option<DateTime<Utc>>,
+    ) -> Result<Event> {
+        let result = sqlx::query_as!(
+            Event,
+            "update events set voting_open = $2, closes_at = $3 where id = $1 returning *",
+            id,
+            voting_open,
+            closes_at
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_now_playing(&self, id: i32, song_id: Option<i32>) -> Result<Event> {
+        let result = sqlx::query_as!(
+            Event,
+            "update events set now_playing = $2 where id = $1 returning *",
+            id,
+            song_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn close_expired_voting(&self) -> Result<Vec<Event>> {
+        let result = sqlx::query_as!(
+            Event,
+            "update events set voting_open = false where voting_open and closes_at <= now() returning *"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_event(&self, id: i32) -> Result<()> {
+        sqlx::query!("delete from events where id = $1", id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_vote_results(&self, event: &Event) -> Result<Vec<VoteResult>> {
+        let songs = sqlx::query_as!(
+            Song,
+            "select s.* from songs s join event_songs es on s.id = es.song_id where es.event_id = $1 and not s.hidden",
+            event.id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let votes = sqlx::query_as!(Vote, "select * from votes where event_id = $1", event.id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tally(event, songs, &votes))
+    }
+
+    async fn get_votes(&self, event_id: i32, session_id: &str) -> Result<Vec<Vote>> {
+        let result = sqlx::query_as!(
+            Vote,
+            "select * from votes where event_id = $1 and session_id = $2",
+            event_id,
+            session_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn count_votes(&self, event_id: i32, session_id: &str) -> Result<i64> {
+        let result = sqlx::query_scalar!(
+            "select count(session_id) from votes where event_id = $1 and session_id = $2",
+            event_id,
+            session_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.unwrap_or(0))
+    }
+
+    async fn count_voters(&self, event_id: i32) -> Result<i64> {
+        let result = sqlx::query_scalar!(
+            "select count(distinct session_id) from votes where event_id = $1",
+            event_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.unwrap_or(0))
+    }
+
+    async fn count_ip_sessions(&self, event_id: i32, ip: &str, session_id: &str) -> Result<i64> {
+        let result = sqlx::query_scalar!(
+            "select count(distinct v.session_id) from votes v join sessions s on v.session_id = s.id where v.event_id = $1 and s.ip = $2 and v.session_id <> $3",
+            event_id,
+            ip,
+            session_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.unwrap_or(0))
+    }
+
+    async fn record_rejected_vote(
+        &self,
+        event_id: i32,
+        session_id: &str,
+        ip: Option<&str>,
+        reason: RejectionReason,
+        count: i32,
+    ) -> Result<()> {
+        sqlx::query!(
+            "insert into rejected_votes (event_id, session_id, ip, reason, count) values ($1, $2, $3, $4, $5)",
+            event_id,
+            session_id,
+            ip,
+            reason.as_str(),
+            count
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_rejected_votes(&self, event_id: i32) -> Result<Vec<RejectedVotes>> {
+        let result = sqlx::query_as!(
+            RejectedVotes,
+            r#"select reason, sum(count) as "count!" from rejected_votes where event_id = $1 group by reason order by reason"#,
+            event_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_vote(
+        &self,
+        event_id: i32,
+        session_id: &str,
+        song_id: i32,
+        vote_budget: i32,
+    ) -> Result<(Song, i32)> {
+        let mut transaction = self.pool.begin().await?;
+
+        // locking the session makes its concurrent votes wait for each other
+        sqlx::query!(
+            "select id from sessions where id = $1 for update",
+            session_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let vote_count = sqlx::query_scalar!(
+            "select count(*) from votes where event_id = $1 and session_id = $2",
+            event_id,
+            session_id
+        )
+        .fetch_one(&mut *transaction)
+        .await?
+        .unwrap_or(0);
+
+        if vote_count >= i64::from(vote_budget) {
+            transaction.rollback().await?;
+            // a vote that could not be cast anyway says why, rather than that the budget is spent
+            return Err(
+                match self.vote_rejection(event_id, session_id, song_id).await? {
+                    VoteError::NotVoted => BadRequestError::TooManyVotes.into(),
+                    rejection => rejection.into(),
+                },
+            );
+        }
+
+        let rank = i32::try_from(vote_count + 1)?;
+
+        let result = sqlx::query_as!(
+            Song,
+            "with inserted_vote as (insert into votes (session_id, song_id, event_id, rank) select $1, $2, $3, $4 where exists (select 1 from event_songs es join songs s on es.song_id = s.id where es.event_id = $3 and es.song_id = $2 and not s.hidden) on conflict (event_id, session_id, song_id) do nothing returning song_id) select s.* from inserted_vote iv join songs s on iv.song_id = s.id",
+            session_id,
+            song_id,
+            event_id,
+            rank
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let Some(song) = result else {
+            transaction.rollback().await?;
+            return Err(self
+                .vote_rejection(event_id, session_id, song_id)
+                .await?
+                .into());
+        };
+
+        transaction.commit().await?;
+
+        Ok((song, rank))
+    }
+
+    async fn delete_vote(&self, event_id: i32, session_id: &str, song_id: i32) -> Result<Song> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query!(
+            "select id from sessions where id = $1 for update",
+            session_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let result = sqlx::query_as!(
+            Song,
+            "with deleted_vote as (delete from votes where event_id = $1 and session_id = $2 and song_id = $3 returning song_id, rank), reranked as (update votes set rank = rank - 1 where event_id = $1 and session_id = $2 and rank > (select rank from deleted_vote)) select s.* from deleted_vote dv join songs s on dv.song_id = s.id",
+            event_id,
+            session_id,
+            song_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        match result {
+            Some(song) => Ok(song),
+            None if self.get_song(song_id).await?.is_none() => Err(VoteError::UnknownSong.into()),
+            None => Err(VoteError::NotVoted.into()),
+        }
+    }
+
+    async fn clear_votes(&self, event_id: i32) -> Result<()> {
+        sqlx::query!("delete from votes where event_id = $1", event_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn add_song(&self, artist: &str, title: &str, description: Option<&str>) -> Result<Song> {
+        let result = sqlx::query_as!(
+            Song,
+            "insert into songs values (default, $1, $2, $3) returning *",
+            artist,
+            title,
+            description
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn add_songs(&self, songs: &[NewSong]) -> Result<Vec<Song>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let mut result = Vec::new();
+        for song in songs {
+            let song = sqlx::query_as!(
+                Song,
+                "insert into songs values (default, $1, $2, $3) returning *",
+                song.artist,
+                song.title,
+                song.description
+            )
+            .fetch_one(&mut *transaction)
+            .await?;
+            result.push(song);
+        }
+
+        transaction.commit().await?;
+
+        Ok(result)
+    }
+
+    async fn delete_song(&self, id: i32) -> Result<()> {
+        sqlx::query!("delete from songs where id = $1", id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn hide_song(&self, id: i32) -> Result<Song> {
+        let song = sqlx::query_as!(
+            Song,
+            "update songs set hidden = true where id = $1 returning *",
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(song)
+    }
+
+    async fn unhide_song(&self, id: i32) -> Result<Song> {
+        let song = sqlx::query_as!(
+            Song,
+            "update songs set hidden = false where id = $1 returning *",
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(song)
+    }
+
+    async fn get_song(&self, id: i32) -> Result<Option<Song>> {
+        let result = sqlx::query_as!(Song, "select * from songs where id = $1", id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn update_song(
+        &self,
+        id: i32,
+        artist: &str,
+        title: &str,
+        description: Option<&str>,
+    ) -> Result<Song> {
+        let song = sqlx::query_as!(
+            Song,
+            "update songs set artist = $2, title = $3, description = $4 where id = $1 returning *",
+            id,
+            artist,
+            title,
+            description
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(song)
+    }
+
+    async fn create_session(&self, id: &str, ip: Option<&str>) -> Result<Session> {
+        let result = sqlx::query_as!(
+            Session,
+            "insert into sessions (id, ip) values ($1, $2) returning *",
+            id,
+            ip
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>> {
+        let result = sqlx::query_as!(Session, "select * from sessions where id = $1", id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn admit_session(&self, event_id: i32, session_id: &str) -> Result<()> {
+        sqlx::query!(
+            "insert into event_admissions (event_id, session_id) values ($1, $2) on conflict do nothing",
+            event_id,
+            session_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn is_admitted(&self, event_id: i32, session_id: &str) -> Result<bool> {
+        let result = sqlx::query_scalar!(
+            r#"select exists (select 1 from event_admissions where event_id = $1 and session_id = $2) as "admitted!""#,
+            event_id,
+            session_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_admin(&self, username: &str, password_hash: &str) -> Result<Admin> {
+        let result = sqlx::query_as!(
+            Admin,
+            "insert into admins values (default, $1, $2) returning *",
+            username,
+            password_hash
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn get_admin(&self, id: i32) -> Result<Option<Admin>> {
+        let result = sqlx::query_as!(Admin, "select * from admins where id = $1", id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_admin_by_username(&self, username: &str) -> Result<Option<Admin>> {
+        let result = sqlx::query_as!(Admin, "select * from admins where username = $1", username)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn create_admin_session(
+        &self,
+        id: &str,
+        admin_id: i32,
+        expires_at: DateTime<Utc>,
+    ) -> Result<AdminSession> {
+        let result = sqlx::query_as!(
+            AdminSession,
+            "insert into admin_sessions (id, admin_id, expires_at) values ($1, $2, $3) returning *",
+            id,
+            admin_id,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn get_admin_session(&self, id: &str) -> Result<Option<AdminSession>> {
+        let result = sqlx::query_as!(
+            AdminSession,
+            "select * from admin_sessions where id = $1",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_admin_session(&self, id: &str) -> Result<()> {
+        sqlx::query!("delete from admin_sessions where id = $1", id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,655 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+
+use super::{
+    Admin, AdminSession, Event, NewSong, RejectedVotes, RejectionReason, Session, Song, Store,
+    Vote, VoteResult, VotingMode,
+};
+use crate::{
+    errors::{BadRequestError, VoteError},
+    tally::tally,
+};
+
+/// Keeps everything in one SQLite file. The queries are checked at runtime, since the
+/// offline query data is for Postgres.
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub async fn connect(url: &str) -> Result<SqliteStore> {
+        let options = url
+            .parse::<SqliteConnectOptions>()
+            .context("The SQLite URL is not valid")?
+            .create_if_missing(true);
+
+        // SQLite has a single writer anyway, and one connection keeps the vote counting
+        // in `create_vote` from racing
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .context("Could not open the SQLite database")?;
+
+        Ok(SqliteStore { pool })
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn migrate(&self) -> Result<()> {
+        sqlx::migrate!("./sqlite_migrations")
+            .run(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_setlist(&self) -> Result<Vec<Song>> {
+        let result = sqlx::query_as("select * from songs order by artist")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_event_setlist(&self, event_id: i32) -> Result<Vec<Song>> {
+        let result = sqlx::query_as(
+            "select s.* from songs s join event_songs es on s.id = es.song_id where es.event_id = ?1 order by s.artist",
+        )
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_event_setlist(&self, event_id: i32, song_ids: &[i32]) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("delete from event_songs where event_id = ?1")
+            .bind(event_id)
+            .execute(&mut *transaction)
+            .await?;
+
+        for song_id in song_ids {
+            sqlx::query(
+                "insert into event_songs (event_id, song_id) values (?1, ?2) on conflict do nothing",
+            )
+            .bind(event_id)
+            .bind(song_id)
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_events(&self) -> Result<Vec<Event>> {
+        let result = sqlx::query_as("select * from events order by date desc, id desc")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_event(&self, id: i32) -> Result<Option<Event>> {
+        let result = sqlx::query_as("select * from events where id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_event_by_slug(&self, slug: &str) -> Result<Option<Event>> {
+        let result = sqlx::query_as("select * from events where slug = ?1")
+            .bind(slug)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_current_event(&self) -> Result<Option<Event>> {
+        let result =
+            sqlx::query_as("select * from events where open order by date desc, id desc limit 1")
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(result)
+    }
+
+    async fn get_song_events(&self, song_id: i32) -> Result<Vec<Event>> {
+        let result = sqlx::query_as(
+            "select e.* from events e join event_songs es on e.id = es.event_id where es.song_id = ?1",
+        )
+        .bind(song_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_event(
+        &self,
+        slug: &str,
+        name: &str,
+        date: NaiveDate,
+        venue: Option<&str>,
+        vote_budget: i32,
+    ) -> Result<Event> {
+        let result = sqlx::query_as(
+            "insert into events (slug, name, date, venue, vote_budget) values (?1, ?2, ?3, ?4, ?5) returning *",
+        )
+        .bind(slug)
+        .bind(name)
+        .bind(date)
+        .bind(venue)
+        .bind(vote_budget)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_event_open(&self, id: i32, open: bool) -> Result<Event> {
+        let result = sqlx::query_as("update events set open = ?2 where id = ?1 returning *")
+            .bind(id)
+            .bind(open)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn update_event_settings(
+        &self,
+        id: i32,
+        vote_budget: i32,
+        voting_mode: VotingMode,
+        max_sessions_per_ip: Option<i32>,
+        join_code: Option<&str>,
+    ) -> Result<Event> {
+        let result = sqlx::query_as(
+            "update events set vote_budget = ?2, voting_mode = ?3, max_sessions_per_ip = ?4, join_code = ?5 where id = ?1 returning *",
+        )
+        .bind(id)
+        .bind(vote_budget)
+        .bind(voting_mode.as_str())
+        .bind(max_sessions_per_ip)
+        .bind(join_code)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_voting_window(
+        &self,
+        id: i32,
+        voting_open: bool,
+        closes_at: Option<DateTime<Utc>>,
+    ) -> Result<Event> {
+        let result = sqlx::query_as(
+            "update events set voting_open = ?2, closes_at = ?3 where id = ?1 returning *",
+        )
+        .bind(id)
+        .bind(voting_open)
+        .bind(closes_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn set_now_playing(&self, id: i32, song_id: Option<i32>) -> Result<Event> {
+        let result = sqlx::query_as("update events set now_playing = ?2 where id = ?1 returning *")
+            .bind(id)
+            .bind(song_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn close_expired_voting(&self) -> Result<Vec<Event>> {
+        // closing times are stored as text, so they are compared here rather than in SQL
+        let scheduled: Vec<Event> =
+            sqlx::query_as("select * from events where voting_open and closes_at is not null")
+                .fetch_all(&self.pool)
+                .await?;
+
+        let mut closed = Vec::new();
+        for event in scheduled {
+            if event.closing_time_passed() {
+                closed.push(
+                    self.set_voting_window(event.id, false, event.closes_at)
+                        .await?,
+                );
+            }
+        }
+
+        Ok(closed)
+    }
+
+    async fn delete_event(&self, id: i32) -> Result<()> {
+        sqlx::query("delete from events where id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_vote_results(&self, event: &Event) -> Result<Vec<VoteResult>> {
+        let songs = sqlx::query_as(
+            "select s.* from songs s join event_songs es on s.id = es.song_id where es.event_id = ?1 and not s.hidden",
+        )
+        .bind(event.id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let votes: Vec<Vote> = sqlx::query_as("select * from votes where event_id = ?1")
+            .bind(event.id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tally(event, songs, &votes))
+    }
+
+    async fn get_votes(&self, event_id: i32, session_id: &str) -> Result<Vec<Vote>> {
+        let result = sqlx::query_as("select * from votes where event_id = ?1 and session_id = ?2")
+            .bind(event_id)
+            .bind(session_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn count_votes(&self, event_id: i32, session_id: &str) -> Result<i64> {
+        let result = sqlx::query_scalar(
+            "select count(*) from votes where event_id = ?1 and session_id = ?2",
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn count_voters(&self, event_id: i32) -> Result<i64> {
+        let result =
+            sqlx::query_scalar("select count(distinct session_id) from votes where event_id = ?1")
+                .bind(event_id)
+                .fetch_one(&self.pool)
+                .await?;
+
+        Ok(result)
+    }
+
+    async fn count_ip_sessions(&self, event_id: i32, ip: &str, session_id: &str) -> Result<i64> {
+        let result = sqlx::query_scalar(
+            "select count(distinct v.session_id) from votes v join sessions s on v.session_id = s.id where v.event_id = ?1 and s.ip = ?2 and v.session_id <> ?3",
+        )
+        .bind(event_id)
+        .bind(ip)
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn record_rejected_vote(
+        &self,
+        event_id: i32,
+        session_id: &str,
+        ip: Option<&str>,
+        reason: RejectionReason,
+        count: i32,
+    ) -> Result<()> {
+        sqlx::query(
+            "insert into rejected_votes (event_id, session_id, ip, reason, count) values (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .bind(ip)
+        .bind(reason.as_str())
+        .bind(count)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_rejected_votes(&self, event_id: i32) -> Result<Vec<RejectedVotes>> {
+        let result = sqlx::query_as(
+            "select reason, sum(count) as count from rejected_votes where event_id = ?1 group by reason order by reason",
+        )
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_vote(
+        &self,
+        event_id: i32,
+        session_id: &str,
+        song_id: i32,
+        vote_budget: i32,
+    ) -> Result<(Song, i32)> {
+        let mut transaction = self.pool.begin().await?;
+
+        let vote_count: i64 = sqlx::query_scalar(
+            "select count(*) from votes where event_id = ?1 and session_id = ?2",
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        let song: Option<(bool, bool, bool)> = sqlx::query_as(
+            "select s.hidden, exists (select 1 from event_songs where event_id = ?1 and song_id = s.id), exists (select 1 from votes where event_id = ?1 and session_id = ?2 and song_id = s.id) from songs s where s.id = ?3",
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .bind(song_id)
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        match song {
+            None => return Err(VoteError::UnknownSong.into()),
+            Some((_, _, true)) => return Err(VoteError::AlreadyVoted.into()),
+            Some((_, false, _)) => return Err(VoteError::NotOnSetlist.into()),
+            Some((true, _, _)) => return Err(VoteError::HiddenSong.into()),
+            Some(_) => {}
+        }
+
+        if vote_count >= i64::from(vote_budget) {
+            return Err(BadRequestError::TooManyVotes.into());
+        }
+
+        let rank = i32::try_from(vote_count + 1)?;
+
+        sqlx::query(
+            "insert into votes (session_id, song_id, event_id, rank) values (?1, ?2, ?3, ?4)",
+        )
+        .bind(session_id)
+        .bind(song_id)
+        .bind(event_id)
+        .bind(rank)
+        .execute(&mut *transaction)
+        .await?;
+
+        let song = sqlx::query_as("select * from songs where id = ?1")
+            .bind(song_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok((song, rank))
+    }
+
+    async fn delete_vote(&self, event_id: i32, session_id: &str, song_id: i32) -> Result<Song> {
+        let mut transaction = self.pool.begin().await?;
+
+        let rank: Option<i32> = sqlx::query_scalar(
+            "delete from votes where event_id = ?1 and session_id = ?2 and song_id = ?3 returning rank",
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .bind(song_id)
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        let Some(rank) = rank else {
+            transaction.rollback().await?;
+            return match self.get_song(song_id).await? {
+                None => Err(VoteError::UnknownSong.into()),
+                Some(_) => Err(VoteError::NotVoted.into()),
+            };
+        };
+
+        sqlx::query(
+            "update votes set rank = rank - 1 where event_id = ?1 and session_id = ?2 and rank > ?3",
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .bind(rank)
+        .execute(&mut *transaction)
+        .await?;
+
+        let song = sqlx::query_as("select * from songs where id = ?1")
+            .bind(song_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(song)
+    }
+
+    async fn clear_votes(&self, event_id: i32) -> Result<()> {
+        sqlx::query("delete from votes where event_id = ?1")
+            .bind(event_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn add_song(&self, artist: &str, title: &str, description: Option<&str>) -> Result<Song> {
+        let result = sqlx::query_as(
+            "insert into songs (artist, title, description) values (?1, ?2, ?3) returning *",
+        )
+        .bind(artist)
+        .bind(title)
+        .bind(description)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn add_songs(&self, songs: &[NewSong]) -> Result<Vec<Song>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let mut result = Vec::new();
+        for song in songs {
+            let song = sqlx::query_as(
+                "insert into songs (artist, title, description) values (?1, ?2, ?3) returning *",
+            )
+            .bind(&song.artist)
+            .bind(&song.title)
+            .bind(&song.description)
+            .fetch_one(&mut *transaction)
+            .await?;
+            result.push(song);
+        }
+
+        transaction.commit().await?;
+
+        Ok(result)
+    }
+
+    async fn delete_song(&self, id: i32) -> Result<()> {
+        sqlx::query("delete from songs where id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn hide_song(&self, id: i32) -> Result<Song> {
+        let song = sqlx::query_as("update songs set hidden = true where id = ?1 returning *")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(song)
+    }
+
+    async fn unhide_song(&self, id: i32) -> Result<Song> {
+        let song = sqlx::query_as("update songs set hidden = false where id = ?1 returning *")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(song)
+    }
+
+    async fn get_song(&self, id: i32) -> Result<Option<Song>> {
+        let result = sqlx::query_as("select * from songs where id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn update_song(
+        &self,
+        id: i32,
+        artist: &str,
+        title: &str,
+        description: Option<&str>,
+    ) -> Result<Song> {
+        let song = sqlx::query_as(
+            "update songs set artist = ?2, title = ?3, description = ?4 where id = ?1 returning *",
+        )
+        .bind(id)
+        .bind(artist)
+        .bind(title)
+        .bind(description)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(song)
+    }
+
+    async fn create_session(&self, id: &str, ip: Option<&str>) -> Result<Session> {
+        let result = sqlx::query_as(
+            "insert into sessions (id, created_at, ip) values (?1, ?2, ?3) returning *",
+        )
+        .bind(id)
+        .bind(Utc::now())
+        .bind(ip)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Option<Session>> {
+        let result = sqlx::query_as("select * from sessions where id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn admit_session(&self, event_id: i32, session_id: &str) -> Result<()> {
+        sqlx::query(
+            "insert into event_admissions (event_id, session_id) values (?1, ?2) on conflict do nothing",
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn is_admitted(&self, event_id: i32, session_id: &str) -> Result<bool> {
+        let result = sqlx::query_scalar(
+            "select exists (select 1 from event_admissions where event_id = ?1 and session_id = ?2)",
+        )
+        .bind(event_id)
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn create_admin(&self, username: &str, password_hash: &str) -> Result<Admin> {
+        let result = sqlx::query_as(
+            "insert into admins (username, password_hash) values (?1, ?2) returning *",
+        )
+        .bind(username)
+        .bind(password_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn get_admin(&self, id: i32) -> Result<Option<Admin>> {
+        let result = sqlx::query_as("select * from admins where id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn get_admin_by_username(&self, username: &str) -> Result<Option<Admin>> {
+        let result = sqlx::query_as("select * from admins where username = ?1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn create_admin_session(
+        &self,
+        id: &str,
+        admin_id: i32,
+        expires_at: DateTime<Utc>,
+    ) -> Result<AdminSession> {
+        let result = sqlx::query_as(
+            "insert into admin_sessions (id, admin_id, expires_at) values (?1, ?2, ?3) returning *",
+        )
+        .bind(id)
+        .bind(admin_id)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn get_admin_session(&self, id: &str) -> Result<Option<AdminSession>> {
+        let result = sqlx::query_as("select * from admin_sessions where id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    async fn delete_admin_session(&self, id: &str) -> Result<()> {
+        sqlx::query("delete from admin_sessions where id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
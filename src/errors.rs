@@ -1,20 +1,214 @@
+use crate::{
+    html,
+    page::page,
+    view::{escape, View},
+};
 use axum::{
-    http::StatusCode,
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
 };
 use thiserror::Error;
+use tracing::error;
 
 #[derive(Error, Debug)]
 pub enum BadRequestError {
-    #[error("Too many votes")]
+    #[error("Du har brugt alle dine stemmer. Tryk på en sang du har stemt på for at fjerne stemmen og stemme på noget andet")]
     TooManyVotes,
+    #[error("Afstemningen er lukket, der kan ikke længere stemmes")]
+    VotingClosed,
+    #[error("Der er allerede stemt fra for mange telefoner på dette netværk")]
+    TooManySessions,
+    #[error("Du skal indtaste arrangementets kode før du kan stemme")]
+    NotAdmitted,
 }
 impl IntoResponse for BadRequestError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Bad request: {}", self),
-        )
-            .into_response()
+        AppError::from(self).into_response()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VoteError {
+    #[error("Sangen findes ikke")]
+    UnknownSong,
+    #[error("Sangen er ikke med på aftenens setliste")]
+    NotOnSetlist,
+    #[error("Sangen er fjernet fra setlisten")]
+    HiddenSong,
+    #[error("Du har allerede stemt på sangen")]
+    AlreadyVoted,
+    #[error("Du har ikke stemt på sangen")]
+    NotVoted,
+}
+
+impl VoteError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            VoteError::AlreadyVoted => StatusCode::CONFLICT,
+            _ => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+/// Failures of stores without a database, matching the database errors mapped in
+/// `AppError::from`.
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("row not found")]
+    NotFound,
+    #[error("duplicate key")]
+    Duplicate,
+}
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("Du er ikke logget ind")]
+    Unauthorized,
+    #[error("Du stemmer for hurtigt, vent et øjeblik og prøv igen")]
+    RateLimited,
+    #[error("Der skete en fejl, prøv igen")]
+    Database(anyhow::Error),
+    #[error(transparent)]
+    BadRequest(#[from] BadRequestError),
+    #[error(transparent)]
+    Vote(#[from] VoteError),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::BadRequest(_) => StatusCode::FORBIDDEN,
+            AppError::Vote(err) => err.status(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<VoteError>() {
+            Ok(err) => return AppError::Vote(err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<BadRequestError>() {
+            Ok(err) => return AppError::BadRequest(err),
+            Err(err) => err,
+        };
+
+        match err.downcast_ref::<StoreError>() {
+            Some(StoreError::NotFound) => return AppError::NotFound("Findes ikke".to_string()),
+            Some(StoreError::Duplicate) => {
+                return AppError::Conflict("Findes allerede".to_string())
+            }
+            None => {}
+        }
+
+        match err.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => AppError::NotFound("Findes ikke".to_string()),
+            Some(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+                AppError::Conflict("Findes allerede".to_string())
+            }
+            _ => AppError::Database(err),
+        }
+    }
+}
+
+/// Marks a response as an error so `error_pages` can wrap it in a full page.
+#[derive(Clone)]
+struct ErrorMessage(String);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        if let AppError::Database(err) = &self {
+            error!("Database error: {:?}", err);
+        }
+
+        let message = self.to_string();
+        let mut response = (self.status(), error_toast(&message)).into_response();
+
+        // error responses only carry the out of band toast
+        response
+            .headers_mut()
+            .insert("HX-Reswap", HeaderValue::from_static("none"));
+        response.extensions_mut().insert(ErrorMessage(message));
+
+        response
+    }
+}
+
+pub fn error_toast(message: &str) -> View {
+    html! {
+        <div hx-swap-oob="beforeend:#toasts">
+            <div
+                role="alert"
+                onclick="this.remove()"
+                class="p-4 w-full max-w-lg text-white bg-red-600 rounded-lg shadow cursor-pointer pointer-events-auto animate-shake"
+            >
+                {escape(message)}
+            </div>
+        </div>
+    }
+}
+
+/// Renders errors as a full page when they are the result of a regular browser navigation.
+pub async fn error_pages(request: Request, next: Next) -> Response {
+    let is_htmx = request.headers().contains_key("HX-Request");
+    let response = next.run(request).await;
+
+    let Some(ErrorMessage(message)) = response.extensions().get::<ErrorMessage>().cloned() else {
+        return response;
+    };
+
+    if is_htmx {
+        return response;
+    }
+
+    let view = page(
+        html! { <h1 class="text-lg">{escape(&message)}</h1> },
+        "Fejl",
+    );
+
+    (response.status(), view).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_errors_keep_their_meaning() {
+        let vote = AppError::from(anyhow::Error::from(VoteError::AlreadyVoted));
+        assert!(matches!(vote, AppError::Vote(VoteError::AlreadyVoted)));
+        assert_eq!(vote.status(), StatusCode::CONFLICT);
+
+        let missing = AppError::from(anyhow::Error::from(StoreError::NotFound));
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        let duplicate = AppError::from(anyhow::Error::from(StoreError::Duplicate));
+        assert_eq!(duplicate.status(), StatusCode::CONFLICT);
+
+        let missing_row = AppError::from(anyhow::Error::from(sqlx::Error::RowNotFound));
+        assert_eq!(missing_row.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn other_errors_do_not_reach_the_guest() {
+        let err = AppError::from(anyhow::anyhow!("connection refused at 10.0.0.5:5432"));
+
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.to_string(), "Der skete en fejl, prøv igen");
     }
 }
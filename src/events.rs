@@ -0,0 +1,805 @@
+use crate::{
+    app::AppState,
+    database::{Database, Event, RejectedVotes, RejectionReason, Song, VotingMode},
+    errors::AppError,
+    html, icons,
+    page::page,
+    updates::{publish, Update},
+    view::{escape, View},
+    vote_results::votes_updated,
+};
+use axum::{
+    extract::{Path, State},
+    Form,
+};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+/// Short enough to type in from a poster, long enough not to be guessed within the rate limit.
+const MIN_JOIN_CODE_LENGTH: usize = 4;
+
+#[derive(Deserialize, Debug)]
+pub struct CreateEventInput {
+    name: String,
+    date: NaiveDate,
+    venue: String,
+}
+
+pub async fn event_by_slug(database: &Database, slug: &str) -> Result<Event, AppError> {
+    database
+        .get_event_by_slug(slug)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Arrangementet findes ikke".to_string()))
+}
+
+pub async fn event_by_id(database: &Database, id: i32) -> Result<Event, AppError> {
+    database
+        .get_event(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Arrangement {} findes ikke", id)))
+}
+
+/// The event `/vote` sends guests to.
+pub async fn current_event(database: &Database) -> Result<Event, AppError> {
+    database
+        .get_current_event()
+        .await?
+        .ok_or_else(|| AppError::NotFound("Der er ingen afstemning i gang lige nu".to_string()))
+}
+
+pub async fn add_event(
+    State(state): State<Arc<AppState>>,
+    Form(input): Form<CreateEventInput>,
+) -> Result<View, AppError> {
+    if input.name.trim().is_empty() {
+        return Err(AppError::Validation(
+            "Arrangementet skal have et navn".to_string(),
+        ));
+    }
+
+    let venue = match input.venue.is_empty() {
+        true => None,
+        false => Some(input.venue.as_str()),
+    };
+
+    let slug = slugify(&format!("{} {}", input.name, input.date));
+
+    let event = state
+        .database
+        .create_event(&slug, &input.name, input.date, venue, state.vote_budget)
+        .await?;
+
+    Ok(event_card(event))
+}
+
+pub async fn delete_event(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(), AppError> {
+    warn!("Deleting event {}", id);
+    state.database.delete_event(id).await?;
+
+    Ok(())
+}
+
+pub async fn open_event(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<View, AppError> {
+    warn!("Opening event {}", id);
+    let event = state.database.set_event_open(id, true).await?;
+
+    votes_updated(&state.tx, &state.database, &event).await;
+
+    Ok(event_card(event))
+}
+
+pub async fn close_event(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<View, AppError> {
+    warn!("Closing event {}", id);
+    let event = state.database.set_event_open(id, false).await?;
+
+    publish(
+        &state.tx,
+        Update::VotingClosed {
+            event: event.slug.clone(),
+        },
+    );
+    votes_updated(&state.tx, &state.database, &event).await;
+
+    Ok(event_card(event))
+}
+
+pub async fn clear_votes(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(), AppError> {
+    warn!("Clearing votes for event {}", id);
+    let event = event_by_id(&state.database, id).await?;
+
+    state.database.clear_votes(event.id).await?;
+
+    votes_updated(&state.tx, &state.database, &event).await;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventSettingsInput {
+    vote_budget: i32,
+    voting_mode: VotingMode,
+    max_sessions_per_ip: String,
+    join_code: String,
+}
+
+pub async fn update_event_settings(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+    Form(input): Form<EventSettingsInput>,
+) -> Result<View, AppError> {
+    if !(1..=100).contains(&input.vote_budget) {
+        return Err(AppError::Validation(
+            "Antal stemmer skal være mellem 1 og 100".to_string(),
+        ));
+    }
+
+    let max_sessions_per_ip = match input.max_sessions_per_ip.trim() {
+        "" => None,
+        value => match value.parse::<i32>() {
+            Ok(cap) if cap >= 1 => Some(cap),
+            _ => {
+                return Err(AppError::Validation(
+                    "Telefoner per netværk skal være et tal over 0, eller tomt".to_string(),
+                ))
+            }
+        },
+    };
+
+    let join_code = Some(input.join_code.trim()).filter(|code| !code.is_empty());
+
+    // QR links carry the code and guests type it in, so it sticks to plain letters and digits
+    if join_code.is_some_and(|code| !code.chars().all(|char| char.is_ascii_alphanumeric())) {
+        return Err(AppError::Validation(
+            "Adgangskoden må kun indeholde bogstaverne a-z og tal".to_string(),
+        ));
+    }
+    if join_code.is_some_and(|code| code.len() < MIN_JOIN_CODE_LENGTH) {
+        return Err(AppError::Validation(format!(
+            "Adgangskoden skal være mindst {} tegn",
+            MIN_JOIN_CODE_LENGTH
+        )));
+    }
+
+    warn!("Updating settings for event {}", id);
+    let event = state
+        .database
+        .update_event_settings(
+            id,
+            input.vote_budget,
+            input.voting_mode,
+            max_sessions_per_ip,
+            join_code,
+        )
+        .await?;
+
+    Ok(settings_form(&event, true))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VotingWindowInput {
+    #[serde(default)]
+    voting_open: bool,
+    closes_at: String,
+}
+
+pub async fn update_voting_window(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+    Form(input): Form<VotingWindowInput>,
+) -> Result<View, AppError> {
+    let closes_at = match input.closes_at.is_empty() {
+        true => None,
+        false => Some(parse_local_time(&input.closes_at)?),
+    };
+
+    if input.voting_open && closes_at.is_some_and(|closes_at| closes_at <= Utc::now()) {
+        return Err(AppError::Validation(
+            "Lukketidspunktet er allerede passeret".to_string(),
+        ));
+    }
+
+    warn!(
+        "Setting voting for event {} open: {}, closes at: {:?}",
+        id, input.voting_open, closes_at
+    );
+    let event = state
+        .database
+        .set_voting_window(id, input.voting_open, closes_at)
+        .await?;
+
+    if !event.accepts_votes() {
+        publish(
+            &state.tx,
+            Update::VotingClosed {
+                event: event.slug.clone(),
+            },
+        );
+    }
+    votes_updated(&state.tx, &state.database, &event).await;
+
+    Ok(voting_window_form(&event, true))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NowPlayingInput {
+    song_id: String,
+}
+
+pub async fn update_now_playing(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+    Form(input): Form<NowPlayingInput>,
+) -> Result<View, AppError> {
+    let song_id = match input.song_id.as_str() {
+        "" => None,
+        value => Some(
+            value
+                .parse::<i32>()
+                .map_err(|_| AppError::Validation("Vælg en sang fra setlisten".to_string()))?,
+        ),
+    };
+
+    warn!("Now playing song {:?} at event {}", song_id, id);
+    let event = state.database.set_now_playing(id, song_id).await?;
+
+    let song = match event.now_playing {
+        Some(song_id) => state.database.get_song(song_id).await?,
+        None => None,
+    };
+
+    publish(
+        &state.tx,
+        Update::NowPlaying {
+            event: event.slug.clone(),
+            song,
+        },
+    );
+
+    let setlist = state.database.get_event_setlist(event.id).await?;
+
+    Ok(now_playing_form(&event, &setlist, true))
+}
+
+/// Closes voting when the scheduled closing time of an event passes.
+pub async fn close_expired_voting(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+
+    loop {
+        interval.tick().await;
+
+        let events = match state.database.close_expired_voting().await {
+            Ok(events) => events,
+            Err(err) => {
+                warn!("Failed to close voting: {}", err);
+                continue;
+            }
+        };
+
+        for event in events {
+            info!("Closed voting for {}", event.slug);
+            publish(
+                &state.tx,
+                Update::VotingClosed {
+                    event: event.slug.clone(),
+                },
+            );
+            votes_updated(&state.tx, &state.database, &event).await;
+        }
+    }
+}
+
+/// Parses the value of a `datetime-local` input, which is in the server's time zone.
+fn parse_local_time(value: &str) -> Result<DateTime<Utc>, AppError> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+        .ok()
+        .and_then(|time| Local.from_local_datetime(&time).single())
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| AppError::Validation("Tidspunktet er ikke gyldigt".to_string()))
+}
+
+pub fn format_local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%H:%M").to_string()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventSetlistInput {
+    #[serde(default)]
+    song_id: Vec<i32>,
+}
+
+pub async fn update_event_setlist(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+    axum_extra::extract::Form(input): axum_extra::extract::Form<EventSetlistInput>,
+) -> Result<View, AppError> {
+    warn!("Updating setlist for event {}", id);
+    let event = event_by_id(&state.database, id).await?;
+
+    state
+        .database
+        .set_event_setlist(event.id, &input.song_id)
+        .await?;
+
+    publish(
+        &state.tx,
+        Update::SetlistChanged {
+            event: event.slug.clone(),
+        },
+    );
+
+    votes_updated(&state.tx, &state.database, &event).await;
+
+    let repertoire = state.database.get_setlist().await?;
+
+    Ok(setlist_form(&event, repertoire, &input.song_id, true))
+}
+
+/// Turns an event name into something usable in `/e/:slug` links.
+fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+
+    for char in name.to_lowercase().chars() {
+        match char {
+            'æ' => slug.push_str("ae"),
+            'ø' => slug.push_str("oe"),
+            'å' => slug.push_str("aa"),
+            char if char.is_ascii_alphanumeric() => slug.push(char),
+            _ if !slug.is_empty() && !slug.ends_with('-') => slug.push('-'),
+            _ => {}
+        }
+    }
+
+    slug.trim_end_matches('-').to_string()
+}
+
+fn event_card(event: Event) -> View {
+    html! {
+        <div
+            id=format!("event-{}", event.id)
+            class=format!(
+                "flex flex-col gap-1 p-4 rounded-lg border shadow dark:border-neutral-700 dark:bg-neutral-950 transition-colors {}",
+                if event.open { Default::default() } else { "text-neutral-500" },
+            )
+        >
+            <div class="flex flex-wrap gap-2 justify-between items-center">
+                <h2 class="text-2xl font-semibold">{escape(&event.name)}</h2>
+
+                <div class="flex gap-4">
+                    {if event.open {
+                        html! {
+                            <button
+                                title="Luk arrangement"
+                                hx-put=format!("/setlist/events/{}/close", event.id)
+                                hx-target=format!("#event-{}", event.id)
+                                hx-swap="outerHTML"
+                            >
+                                {icons::eye_off()}
+                            </button>
+                        }
+                    } else {
+                        html! {
+                            <button
+                                title="Åbn arrangement"
+                                hx-put=format!("/setlist/events/{}/open", event.id)
+                                hx-target=format!("#event-{}", event.id)
+                                hx-swap="outerHTML"
+                            >
+                                {icons::eye()}
+                            </button>
+                        }
+                    }}
+                    <button
+                        title="Slet arrangement"
+                        hx-delete=format!("/setlist/events/{}", event.id)
+                        hx-target=format!("#event-{}", event.id)
+                        hx-swap="outerHTML"
+                        hx-confirm="Slet arrangementet og alle dets stemmer?"
+                        class="text-red-500"
+                    >
+                        {icons::trash_2()}
+                    </button>
+                </div>
+            </div>
+            <h4 class="text-sm">
+                {event.date.format("%d-%m-%Y").to_string()}
+                {if let Some(venue) = &event.venue {
+                    format!(", {}", escape(venue))
+                } else {
+                    Default::default()
+                }}
+            </h4>
+            <div class="flex flex-wrap gap-4 pt-2">
+                <a class="text-blue-500" href=format!("/setlist/events/{}", event.id)>
+                    Setliste
+                </a>
+                <a class="text-blue-500" href=format!("/e/{}/vote", event.slug)>
+                    Stemmeside
+                </a>
+                <a class="text-blue-500" href=format!("/e/{}/results", event.slug)>
+                    Resultater
+                </a>
+                <button
+                    class="text-red-500"
+                    hx-post=format!("/setlist/events/{}/votes/clear", event.id)
+                    hx-swap="none"
+                    hx-confirm="Slet alle stemmer for arrangementet?"
+                >
+                    Slet alle stemmer
+                </button>
+            </div>
+        </div>
+    }
+}
+
+pub async fn events_page(State(state): State<Arc<AppState>>) -> Result<View, AppError> {
+    let events = state
+        .database
+        .get_events()
+        .await?
+        .into_iter()
+        .map(event_card)
+        .collect::<View>();
+
+    let event_container = html! {
+        <div class="flex flex-col gap-3 w-full max-w-lg">
+            <a class="text-blue-500" href="/setlist">
+                Repertoire
+            </a>
+            <details id="add-event">
+                <form
+                    id="add-event-form"
+                    hx-post="/setlist/events"
+                    hx-target="#add-event"
+                    hx-swap="afterend"
+                    class="flex flex-col gap-3 p-4 mb-3 max-w-lg rounded-lg border shadow text-neutral-500 dark:border-neutral-700 dark:bg-neutral-950"
+                >
+                    <label class="dark:text-white" for="name">
+                        {"Name:"}
+                    </label>
+                    <input class="p-1 rounded bg-neutral-300" type="text" id="name" name="name" />
+                    <label class="dark:text-white" for="date">
+                        {"Date:"}
+                    </label>
+                    <input class="p-1 rounded bg-neutral-300" type="date" id="date" name="date" />
+                    <label class="dark:text-white" for="venue">
+                        {"Venue:"}
+                    </label>
+                    <input class="p-1 rounded bg-neutral-300" type="text" id="venue" name="venue" />
+
+                    <input
+                        class="p-1 text-white bg-blue-500 rounded transition-colors hover:bg-blue-400"
+                        type="submit"
+                        value="Submit"
+                    />
+                </form>
+                <summary class="flex flex-col items-center p-4 max-w-lg rounded-lg border shadow transition-colors cursor-pointer hover:text-white text-neutral-500 dark:border-neutral-700 dark:bg-neutral-950">
+                    {icons::plus_circle()}
+                </summary>
+            </details>
+            {events}
+        </div>
+    };
+
+    Ok(page(event_container, "Arrangementer"))
+}
+
+fn setlist_form(event: &Event, repertoire: Vec<Song>, selected: &[i32], saved: bool) -> View {
+    let songs = repertoire
+        .into_iter()
+        .map(|song| {
+            html! {
+                <label
+                    class=format!(
+                        "flex gap-3 items-center p-3 rounded-lg border shadow cursor-pointer dark:border-neutral-700 dark:bg-neutral-950 {}",
+                        if song.hidden { "text-neutral-500" } else { Default::default() },
+                    )
+                >
+                    <input
+                        type="checkbox"
+                        name="song_id"
+                        value=song.id
+                        {if selected.contains(&song.id) { "checked" } else { Default::default() }}
+                    />
+                    <div class="flex flex-col">
+                        <span class="font-semibold">{escape(&song.title)}</span>
+                        <span class="text-sm text-neutral-500">{escape(&song.artist)}</span>
+                    </div>
+                </label>
+            }
+        })
+        .collect::<View>();
+
+    html! {
+        <form
+            id="event-setlist"
+            hx-post=format!("/setlist/events/{}/songs", event.id)
+            hx-swap="outerHTML"
+            class="flex flex-col gap-3"
+        >
+            <div class="flex gap-4 justify-end text-blue-500">
+                <button
+                    type="button"
+                    onclick="this.form.querySelectorAll('[name=song_id]').forEach(c => c.checked = true)"
+                >
+                    Vælg alle
+                </button>
+                <button
+                    type="button"
+                    onclick="this.form.querySelectorAll('[name=song_id]').forEach(c => c.checked = false)"
+                >
+                    Fravælg alle
+                </button>
+            </div>
+            {songs}
+            <input
+                class="sticky bottom-4 p-2 text-white bg-blue-500 rounded transition-colors cursor-pointer hover:bg-blue-400"
+                type="submit"
+                value=if saved { "Gemt" } else { "Gem setliste" }
+            />
+        </form>
+    }
+}
+
+fn settings_form(event: &Event, saved: bool) -> View {
+    html! {
+        <form
+            id="event-settings"
+            hx-post=format!("/setlist/events/{}/settings", event.id)
+            hx-swap="outerHTML"
+            class="flex flex-col gap-3 p-4 rounded-lg border shadow dark:border-neutral-700 dark:bg-neutral-950"
+        >
+            <label for="vote_budget">{"Stemmer per gæst:"}</label>
+            <input
+                class="p-1 text-black rounded bg-neutral-300"
+                type="number"
+                min="1"
+                max="100"
+                id="vote_budget"
+                name="vote_budget"
+                value=event.vote_budget
+            />
+            <label for="voting_mode">{"Afstemningsform:"}</label>
+            <select class="p-1 text-black rounded bg-neutral-300" id="voting_mode" name="voting_mode">
+                {[
+                    (VotingMode::Approval, "Almindelig, hver stemme tæller én"),
+                    (VotingMode::Borda, "Point efter prioritet (Borda)"),
+                    (VotingMode::InstantRunoff, "Prioriteret udskilning (instant-runoff)"),
+                ]
+                    .into_iter()
+                    .map(|(mode, label)| {
+                        html! {
+                            <option
+                                value=mode.as_str()
+                                {if mode == event.voting_mode { "selected" } else { Default::default() }}
+                            >
+                                {label}
+                            </option>
+                        }
+                    })
+                    .collect::<View>()}
+            </select>
+            <label for="max_sessions_per_ip">{"Telefoner per netværk (tom for ubegrænset):"}</label>
+            <input
+                class="p-1 text-black rounded bg-neutral-300"
+                type="number"
+                min="1"
+                id="max_sessions_per_ip"
+                name="max_sessions_per_ip"
+                value=event.max_sessions_per_ip.map(|cap| cap.to_string()).unwrap_or_default()
+            />
+            <label for="join_code">{"Adgangskode (tom for ingen):"}</label>
+            <input
+                class="p-1 text-black rounded bg-neutral-300"
+                type="text"
+                id="join_code"
+                name="join_code"
+                autocomplete="off"
+                value=event.join_code.as_deref().unwrap_or_default()
+            />
+            {match &event.join_code {
+                Some(code) => {
+                    let link = format!("/e/{}/join?code={}", event.slug, code);
+                    html! {
+                        <p class="text-sm text-neutral-500">
+                            {"Link til QR-kode: "}
+                            <a class="text-blue-500 break-all" href=&link>{&link}</a>
+                        </p>
+                    }
+                }
+                None => Default::default(),
+            }}
+            <input
+                class="p-1 text-white bg-blue-500 rounded transition-colors cursor-pointer hover:bg-blue-400"
+                type="submit"
+                value=if saved { "Gemt" } else { "Gem indstillinger" }
+            />
+        </form>
+    }
+}
+
+fn voting_window_form(event: &Event, saved: bool) -> View {
+    let closes_at = event
+        .closes_at
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%dT%H:%M")
+                .to_string()
+        })
+        .unwrap_or_default();
+
+    html! {
+        <form
+            id="voting-window"
+            hx-post=format!("/setlist/events/{}/voting", event.id)
+            hx-swap="outerHTML"
+            class="flex flex-col gap-3 p-4 rounded-lg border shadow dark:border-neutral-700 dark:bg-neutral-950"
+        >
+            <label class="flex gap-3 items-center">
+                <input
+                    type="checkbox"
+                    name="voting_open"
+                    value="true"
+                    {if event.voting_open { "checked" } else { Default::default() }}
+                />
+                {"Åben for stemmer"}
+            </label>
+            <label for="closes_at">{"Luk automatisk:"}</label>
+            <input
+                class="p-1 text-black rounded bg-neutral-300"
+                type="datetime-local"
+                id="closes_at"
+                name="closes_at"
+                value=closes_at
+            />
+            <p class="text-sm text-neutral-500">
+                {if event.accepts_votes() {
+                    "Gæster kan stemme nu"
+                } else {
+                    "Afstemningen er lukket"
+                }}
+            </p>
+            <input
+                class="p-1 text-white bg-blue-500 rounded transition-colors cursor-pointer hover:bg-blue-400"
+                type="submit"
+                value=if saved { "Gemt" } else { "Gem afstemning" }
+            />
+        </form>
+    }
+}
+
+fn now_playing_form(event: &Event, setlist: &[Song], saved: bool) -> View {
+    html! {
+        <form
+            id="now-playing"
+            hx-post=format!("/setlist/events/{}/now-playing", event.id)
+            hx-swap="outerHTML"
+            class="flex flex-col gap-3 p-4 rounded-lg border shadow dark:border-neutral-700 dark:bg-neutral-950"
+        >
+            <label for="song_id">{"Spiller nu:"}</label>
+            <select class="p-1 text-black rounded bg-neutral-300" id="song_id" name="song_id">
+                <option value="">Ingen</option>
+                {setlist
+                    .iter()
+                    .map(|song| {
+                        html! {
+                            <option
+                                value=song.id
+                                {if event.now_playing == Some(song.id) { "selected" } else { Default::default() }}
+                            >
+                                {escape(&format!("{} - {}", song.title, song.artist))}
+                            </option>
+                        }
+                    })
+                    .collect::<View>()}
+            </select>
+            <input
+                class="p-1 text-white bg-blue-500 rounded transition-colors cursor-pointer hover:bg-blue-400"
+                type="submit"
+                value=if saved { "Gemt" } else { "Vis sang" }
+            />
+        </form>
+    }
+}
+
+fn rejected_votes(rejected: Vec<RejectedVotes>) -> View {
+    if rejected.is_empty() {
+        return html! {
+            <p class="text-sm text-neutral-500">Ingen stemmer er blevet afvist</p>
+        };
+    }
+
+    html! {
+        <div class="flex flex-col gap-1 p-4 rounded-lg border shadow dark:border-neutral-700 dark:bg-neutral-950">
+            <h2 class="font-semibold">Afviste stemmer</h2>
+            {rejected
+                .into_iter()
+                .map(|rejected| {
+                    let label = match rejected.reason {
+                        RejectionReason::IpRateLimit => "For mange stemmer fra samme netværk",
+                        RejectionReason::SessionRateLimit => "For mange stemmer fra samme telefon",
+                        RejectionReason::SessionsPerIp => "For mange telefoner på samme netværk",
+                    };
+
+                    html! {
+                        <div class="flex justify-between">
+                            <span>{label}</span>
+                            <span>{rejected.count}</span>
+                        </div>
+                    }
+                })
+                .collect::<View>()}
+        </div>
+    }
+}
+
+pub async fn event_page(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<View, AppError> {
+    let event = event_by_id(&state.database, id).await?;
+
+    let setlist = state.database.get_event_setlist(event.id).await?;
+    let selected: Vec<i32> = setlist.iter().map(|song| song.id).collect();
+
+    let repertoire = state.database.get_setlist().await?;
+    let rejected = state.database.get_rejected_votes(event.id).await?;
+
+    let event_container = html! {
+        <div class="flex flex-col gap-3 w-full max-w-lg">
+            <a class="text-blue-500" href="/setlist/events">
+                Arrangementer
+            </a>
+            <h1 class="text-2xl font-semibold">{escape(&event.name)}</h1>
+            <a class="text-blue-500" href=format!("/setlist/events/{}/qr", event.id)>
+                Vis QR-kode
+            </a>
+            <a class="text-blue-500" href=format!("/setlist/events/{}/present", event.id)>
+                Vis resultater på storskærm
+            </a>
+            {settings_form(&event, false)}
+            {voting_window_form(&event, false)}
+            {now_playing_form(&event, &setlist, false)}
+            {rejected_votes(rejected)}
+            <p class="text-neutral-500">
+                Vælg hvilke sange fra repertoiret der kan stemmes på til arrangementet
+            </p>
+            {setlist_form(&event, repertoire, &selected, false)}
+        </div>
+    };
+
+    Ok(page(event_container, &event.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::slugify;
+
+    #[test]
+    fn slugs_spell_out_danish_letters() {
+        assert_eq!(
+            slugify("Sommerfest på Ærø 2030-06-01"),
+            "sommerfest-paa-aeroe-2030-06-01"
+        );
+        assert_eq!(slugify("Grøn Koncert"), "groen-koncert");
+    }
+
+    #[test]
+    fn slugs_collapse_and_trim_punctuation() {
+        assert_eq!(slugify("  Rock & Roll!! "), "rock-roll");
+        assert_eq!(slugify("--Jazz--"), "jazz");
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{
+    app::AppState,
+    database::{Event, VoteResult},
+    errors::AppError,
+    events::{current_event, event_by_slug},
+    html,
+    view::{escape, View},
+    vote_results::results_and_voters,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, sync::Arc};
+
+/// Black on white with big type, so the setlist can be read from the floor of the stage.
+const PRINT_STYLE: &str = "
+    body { margin: 0 auto; max-width: 50rem; padding: 1.5rem; font-family: sans-serif; }
+    body { color: black; background: white; }
+    h1 { margin: 0; font-size: 2.5rem; }
+    .details { margin-top: 0.25rem; color: #555; }
+    ol { padding-left: 3.5rem; font-size: 2rem; line-height: 1.4; }
+    .artist { margin-left: 0.75rem; color: #555; font-size: 1.25rem; }
+    button { padding: 0.5rem 1rem; font-size: 1rem; cursor: pointer; }
+    @page { margin: 1.5cm; }
+    @media print { button { display: none; } }
+";
+
+#[derive(Serialize)]
+struct ExportedResult<'a> {
+    position: usize,
+    title: &'a str,
+    artist: &'a str,
+    votes: i64,
+    points: i64,
+}
+
+#[derive(Serialize)]
+struct ExportedResults<'a> {
+    event: &'a str,
+    slug: &'a str,
+    date: NaiveDate,
+    voters: i64,
+    results: Vec<ExportedResult<'a>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PrintQuery {
+    /// Only prints the best `top` songs.
+    top: Option<usize>,
+}
+
+fn exported_results<'a>(
+    event: &'a Event,
+    results: &'a [VoteResult],
+    voters: i64,
+) -> ExportedResults<'a> {
+    ExportedResults {
+        event: &event.name,
+        slug: &event.slug,
+        date: event.date,
+        voters,
+        results: results
+            .iter()
+            .enumerate()
+            .map(|(index, result)| ExportedResult {
+                position: index + 1,
+                title: &result.song.title,
+                artist: &result.song.artist,
+                votes: result.vote_count,
+                points: result.points,
+            })
+            .collect(),
+    }
+}
+
+/// Keeps spreadsheet programs from running song titles like `=HYPERLINK(...)` as formulas.
+fn spreadsheet_text(text: &str) -> Cow<'_, str> {
+    if text.starts_with(['=', '+', '-', '@']) {
+        Cow::Owned(format!("'{}", text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+fn results_csv(event: &Event, results: &[VoteResult]) -> Result<Response, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    writer
+        .write_record(["Placering", "Titel", "Kunstner", "Stemmer", "Point"])
+        .map_err(anyhow::Error::from)?;
+    for (index, result) in results.iter().enumerate() {
+        writer
+            .write_record([
+                &*(index + 1).to_string(),
+                &*spreadsheet_text(&result.song.title),
+                &*spreadsheet_text(&result.song.artist),
+                &*result.vote_count.to_string(),
+                &*result.points.to_string(),
+            ])
+            .map_err(anyhow::Error::from)?;
+    }
+
+    let rows = writer
+        .into_inner()
+        .map_err(|err| anyhow::anyhow!("Failed to write results: {}", err))?;
+
+    // the byte order mark makes spreadsheet programs read æ, ø and å correctly
+    let mut csv = "\u{feff}".as_bytes().to_vec();
+    csv.extend(rows);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}-resultater.csv\"", event.slug),
+            ),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+/// A plain page of the songs in the order they placed, made for printing the stage setlist.
+fn results_print(event: &Event, results: &[VoteResult], voters: i64, top: Option<usize>) -> View {
+    let doctype = "<!DOCTYPE html>";
+    let style = format!("<style>{}</style>", PRINT_STYLE);
+
+    let songs = results
+        .iter()
+        .take(top.unwrap_or(results.len()))
+        .map(|result| {
+            html! {
+                <li>
+                    {escape(&result.song.title)}
+                    <span class="artist">{escape(&result.song.artist)}</span>
+                </li>
+            }
+        })
+        .collect::<View>();
+
+    html! {
+        {doctype}
+
+        <html lang="da">
+            <head>
+                <meta charset="utf-8" />
+                <title>{format!("Setliste {}", escape(&event.name))}</title>
+                {style}
+            </head>
+            <body>
+                <h1>{escape(&event.name)}</h1>
+                <p class="details">
+                    {format!("{}, {} har stemt", event.date.format("%d.%m.%Y"), voters)}
+                </p>
+                <ol>{songs}</ol>
+                <button onclick="window.print()">Udskriv</button>
+            </body>
+        </html>
+    }
+}
+
+pub async fn current_results_csv(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let event = current_event(&state.database).await?;
+    let results = state.database.get_vote_results(&event).await?;
+
+    results_csv(&event, &results)
+}
+
+pub async fn current_results_json(
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, AppError> {
+    let event = current_event(&state.database).await?;
+    let (results, voters) = results_and_voters(&state.database, &event).await?;
+
+    Ok(Json(exported_results(&event, &results, voters)).into_response())
+}
+
+pub async fn current_results_print(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<PrintQuery>,
+) -> Result<View, AppError> {
+    let event = current_event(&state.database).await?;
+    let (results, voters) = results_and_voters(&state.database, &event).await?;
+
+    Ok(results_print(&event, &results, voters, query.top))
+}
+
+pub async fn event_results_csv(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<Response, AppError> {
+    let event = event_by_slug(&state.database, &slug).await?;
+    let results = state.database.get_vote_results(&event).await?;
+
+    results_csv(&event, &results)
+}
+
+pub async fn event_results_json(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<Response, AppError> {
+    let event = event_by_slug(&state.database, &slug).await?;
+    let (results, voters) = results_and_voters(&state.database, &event).await?;
+
+    Ok(Json(exported_results(&event, &results, voters)).into_response())
+}
+
+pub async fn event_results_print(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+    Query(query): Query<PrintQuery>,
+) -> Result<View, AppError> {
+    let event = event_by_slug(&state.database, &slug).await?;
+    let (results, voters) = results_and_voters(&state.database, &event).await?;
+
+    Ok(results_print(&event, &results, voters, query.top))
+}
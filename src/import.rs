@@ -0,0 +1,276 @@
+use crate::{
+    database::{NewSong, Song},
+    errors::AppError,
+};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// The kinds of files the repertoire can be imported from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    /// Columns title, artist and description, with or without a header row.
+    Csv,
+    /// A list of objects with a title, an artist and maybe a description.
+    Json,
+    /// A playlist, taking the songs from its `#EXTINF` lines.
+    M3u,
+    /// One `Artist - Title` per line.
+    Text,
+}
+
+impl ImportFormat {
+    /// Picks the format from the extension of a file, treating unknown files as plain text.
+    pub fn from_file_name(name: &str) -> ImportFormat {
+        let extension = name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_lowercase());
+
+        match extension.as_deref() {
+            Some("csv") => ImportFormat::Csv,
+            Some("json") => ImportFormat::Json,
+            Some("m3u" | "m3u8") => ImportFormat::M3u,
+            _ => ImportFormat::Text,
+        }
+    }
+}
+
+/// Why a song in an import is left out unless the admin picks it anyway.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Duplicate {
+    /// The repertoire already has the song.
+    Existing,
+    /// The song is further up in the same file.
+    InFile,
+}
+
+pub struct ImportRow {
+    pub song: NewSong,
+    pub duplicate: Option<Duplicate>,
+}
+
+/// Reads the songs in a file, failing on the first one without both a title and an artist.
+pub fn parse_songs(format: ImportFormat, contents: &str) -> Result<Vec<NewSong>, AppError> {
+    // spreadsheet programs like to start their exports with a byte order mark
+    let contents = contents.trim_start_matches('\u{feff}');
+
+    match format {
+        ImportFormat::Csv => parse_csv(contents),
+        ImportFormat::Json => parse_json(contents),
+        ImportFormat::M3u => parse_m3u(contents),
+        ImportFormat::Text => parse_text(contents),
+    }
+}
+
+/// Marks the songs that are in the repertoire or earlier in the file, comparing artist and
+/// title without regard to case.
+pub fn find_duplicates(songs: Vec<NewSong>, repertoire: &[Song]) -> Vec<ImportRow> {
+    let key = |artist: &str, title: &str| (artist.to_lowercase(), title.to_lowercase());
+
+    let existing: HashSet<_> = repertoire
+        .iter()
+        .map(|song| key(&song.artist, &song.title))
+        .collect();
+    let mut seen = HashSet::new();
+
+    songs
+        .into_iter()
+        .map(|song| {
+            let song_key = key(&song.artist, &song.title);
+
+            let duplicate = if existing.contains(&song_key) {
+                Some(Duplicate::Existing)
+            } else if !seen.insert(song_key) {
+                Some(Duplicate::InFile)
+            } else {
+                None
+            };
+
+            ImportRow { song, duplicate }
+        })
+        .collect()
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn new_song(
+    place: String,
+    title: Option<&str>,
+    artist: Option<&str>,
+    description: Option<&str>,
+) -> Result<NewSong, AppError> {
+    match (non_empty(title), non_empty(artist)) {
+        (Some(title), Some(artist)) => Ok(NewSong {
+            artist,
+            title,
+            description: non_empty(description),
+        }),
+        _ => Err(AppError::Validation(format!(
+            "{} mangler en titel eller en kunstner",
+            place
+        ))),
+    }
+}
+
+/// Splits a line like `Kim Larsen - Papirsklip` into a song.
+fn song_from_line(number: usize, line: &str) -> Result<NewSong, AppError> {
+    match line.split_once(" - ") {
+        Some((artist, title)) => {
+            new_song(format!("Linje {}", number), Some(title), Some(artist), None)
+        }
+        None => Err(AppError::Validation(format!(
+            "Linje {} er ikke på formen Kunstner - Titel",
+            number
+        ))),
+    }
+}
+
+/// The positions of the columns in a CSV file.
+struct Columns {
+    title: usize,
+    artist: usize,
+    description: Option<usize>,
+}
+
+impl Columns {
+    /// Files without a header row are read as title, artist, description.
+    const WITHOUT_HEADER: Columns = Columns {
+        title: 0,
+        artist: 1,
+        description: Some(2),
+    };
+
+    /// Finds the columns by name if `row` is a header, also knowing the names used by
+    /// Danish spreadsheets and Spotify playlist exports.
+    fn from_header(row: &csv::StringRecord) -> Option<Columns> {
+        let find = |names: &[&str]| {
+            row.iter()
+                .position(|column| names.contains(&column.to_lowercase().as_str()))
+        };
+
+        Some(Columns {
+            title: find(&["title", "titel", "sang", "name", "track name"])?,
+            artist: find(&["artist", "kunstner", "artist name", "artist name(s)"])?,
+            description: find(&["description", "beskrivelse", "note"]),
+        })
+    }
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<NewSong>, AppError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+
+    let mut columns = Columns::WITHOUT_HEADER;
+    let mut songs = Vec::new();
+
+    for (index, row) in reader.records().enumerate() {
+        let row =
+            row.map_err(|_| AppError::Validation(format!("Række {} kan ikke læses", index + 1)))?;
+
+        if index == 0 {
+            if let Some(header) = Columns::from_header(&row) {
+                columns = header;
+                continue;
+            }
+        }
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+
+        songs.push(new_song(
+            format!("Række {}", index + 1),
+            row.get(columns.title),
+            row.get(columns.artist),
+            columns.description.and_then(|column| row.get(column)),
+        )?);
+    }
+
+    Ok(songs)
+}
+
+#[derive(Deserialize)]
+struct JsonSong {
+    #[serde(alias = "titel", alias = "name")]
+    title: Option<String>,
+    #[serde(alias = "kunstner")]
+    artist: Option<String>,
+    #[serde(alias = "beskrivelse")]
+    description: Option<String>,
+}
+
+fn parse_json(contents: &str) -> Result<Vec<NewSong>, AppError> {
+    let songs: Vec<JsonSong> = serde_json::from_str(contents).map_err(|err| {
+        // the error can quote the file, so only its position is shown
+        AppError::Validation(format!(
+            "Filen skal være en JSON-liste af sange med titel og kunstner, fejl i linje {}",
+            err.line()
+        ))
+    })?;
+
+    songs
+        .iter()
+        .enumerate()
+        .map(|(index, song)| {
+            new_song(
+                format!("Sang {}", index + 1),
+                song.title.as_deref(),
+                song.artist.as_deref(),
+                song.description.as_deref(),
+            )
+        })
+        .collect()
+}
+
+fn parse_m3u(contents: &str) -> Result<Vec<NewSong>, AppError> {
+    let mut songs = Vec::new();
+    let mut info = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        // `#EXTINF:<seconds>,Artist - Title` describes the entry on the next line
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            let name = extinf.split_once(',').map(|(_, name)| name).unwrap_or("");
+            info = Some((index + 1, name));
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // entries without `#EXTINF` are usually named after the song
+        let (number, name) = info.take().unwrap_or((index + 1, file_stem(line)));
+        songs.push(song_from_line(number, name)?);
+    }
+
+    Ok(songs)
+}
+
+/// The name of the file at the end of a path or URL, without its extension.
+fn file_stem(path: &str) -> &str {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+
+    match name.rsplit_once('.') {
+        Some((stem, extension))
+            if extension.len() <= 4 && extension.chars().all(char::is_alphanumeric) =>
+        {
+            stem
+        }
+        _ => name,
+    }
+}
+
+fn parse_text(contents: &str) -> Result<Vec<NewSong>, AppError> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| song_from_line(index + 1, line))
+        .collect()
+}
@@ -0,0 +1,133 @@
+use crate::{
+    app::AppState,
+    database::{Database, Event},
+    errors::{AppError, BadRequestError},
+    events::event_by_slug,
+    html,
+    page::page,
+    rate_limit::{limit_join_attempts, ClientIp},
+    session::Session,
+    view::{escape, View},
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+#[derive(Deserialize, Debug)]
+pub struct JoinInput {
+    code: Option<String>,
+}
+
+/// Whether a session may vote at an event, which it always may when the event has no join code.
+pub async fn is_admitted(
+    database: &Database,
+    event: &Event,
+    session_id: &str,
+) -> Result<bool, AppError> {
+    match event.join_code {
+        Some(_) => Ok(database.is_admitted(event.id, session_id).await?),
+        None => Ok(true),
+    }
+}
+
+pub async fn ensure_admitted(
+    database: &Database,
+    event: &Event,
+    session_id: &str,
+) -> Result<(), AppError> {
+    match is_admitted(database, event, session_id).await? {
+        true => Ok(()),
+        false => Err(BadRequestError::NotAdmitted.into()),
+    }
+}
+
+/// Admits guests arriving with `?code=` from a QR link, and asks everyone else for the code.
+pub async fn join_page(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+    session: Session,
+    ClientIp(ip): ClientIp,
+    Query(input): Query<JoinInput>,
+) -> Result<Response, AppError> {
+    join(&state, &slug, &session, ip.as_deref(), input.code).await
+}
+
+pub async fn join_event(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+    session: Session,
+    ClientIp(ip): ClientIp,
+    Form(input): Form<JoinInput>,
+) -> Result<Response, AppError> {
+    join(&state, &slug, &session, ip.as_deref(), input.code).await
+}
+
+async fn join(
+    state: &AppState,
+    slug: &str,
+    session: &Session,
+    ip: Option<&str>,
+    code: Option<String>,
+) -> Result<Response, AppError> {
+    let event = event_by_slug(&state.database, slug).await?;
+    let vote_page = Redirect::to(&format!("/e/{}/vote", event.slug));
+
+    if is_admitted(&state.database, &event, &session.id).await? {
+        return Ok(vote_page.into_response());
+    }
+
+    let Some(code) = code else {
+        return Ok(page(join_form(&event, false), &event.name).into_response());
+    };
+
+    limit_join_attempts(&state.vote_guard, &event, session, ip)?;
+
+    if !event.join_code_matches(&code) {
+        warn!("Wrong join code for {} by {}", event.slug, session.id);
+        return Ok(page(join_form(&event, true), &event.name).into_response());
+    }
+
+    info!("Admitted {} to {}", session.id, event.slug);
+    state.database.admit_session(event.id, &session.id).await?;
+
+    Ok(vote_page.into_response())
+}
+
+fn join_form(event: &Event, failed: bool) -> View {
+    html! {
+        <form
+            method="post"
+            action=format!("/e/{}/join", event.slug)
+            class="flex flex-col gap-3 p-4 w-full max-w-lg rounded-lg border shadow dark:border-neutral-700 dark:bg-neutral-950"
+        >
+            <h1 class="text-lg">{escape(&event.name)}</h1>
+            <p class="text-neutral-500">
+                Indtast koden du kan finde ved scenen for at stemme
+            </p>
+            {if failed {
+                html! { <p class="text-red-500">Forkert kode, prøv igen</p> }
+            } else {
+                Default::default()
+            }}
+            <label for="code">Kode:</label>
+            <input
+                class="p-1 text-black rounded bg-neutral-300"
+                type="text"
+                id="code"
+                name="code"
+                autocomplete="off"
+                autocapitalize="characters"
+            />
+            <input
+                class="p-1 text-white bg-blue-500 rounded transition-colors hover:bg-blue-400"
+                type="submit"
+                value="Stem"
+            />
+        </form>
+    }
+}
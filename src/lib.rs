@@ -1,9 +1,23 @@
+pub mod admin;
+pub mod api;
 pub mod app;
+pub mod cli;
+pub mod config;
 pub mod database;
 pub mod errors;
+pub mod events;
+pub mod export;
 pub mod icons;
+pub mod import;
+pub mod join;
 pub mod page;
+pub mod presenter;
+pub mod qr;
+pub mod rate_limit;
+pub mod session;
 pub mod setlist;
+pub mod tally;
+pub mod updates;
 pub mod view;
 pub mod vote;
 pub mod vote_results;
@@ -1,6 +1,6 @@
-use setlist_requests::app::serve_app;
+use setlist_requests::cli::run;
 
 #[tokio::main]
 async fn main() {
-    serve_app().await;
+    run().await;
 }
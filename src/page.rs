@@ -1,14 +1,37 @@
-use crate::{html, view::View};
+use crate::{
+    config::Branding,
+    html,
+    view::{escape, View},
+};
+use std::sync::OnceLock;
+
+static BRANDING: OnceLock<Branding> = OnceLock::new();
+
+/// Sets the branding every page shows, once at startup.
+pub fn set_branding(branding: Branding) {
+    if BRANDING.set(branding).is_err() {
+        tracing::warn!("Branding was already set");
+    }
+}
+
+pub fn branding() -> &'static Branding {
+    BRANDING.get_or_init(Branding::default)
+}
 
 pub fn page(component: View, title: &str) -> View {
-    page_inner(component, View::default(), title)
+    page_inner(component, View::default(), title, true)
 }
 
 pub fn page_with_view_before(component: View, before: View, title: &str) -> View {
-    page_inner(component, before, title)
+    page_inner(component, before, title, true)
+}
+
+/// A page without the navigation bar, for the stage projector.
+pub fn fullscreen_page(component: View, title: &str) -> View {
+    page_inner(component, View::default(), title, false)
 }
 
-fn page_inner(component: View, before: View, title: &str) -> View {
+fn page_inner(component: View, before: View, title: &str, nav: bool) -> View {
     let style_url = "/assets/styles.css?version=14";
     let doctype = "<!DOCTYPE html>";
 
@@ -17,7 +40,7 @@ fn page_inner(component: View, before: View, title: &str) -> View {
 
         <html lang="en" class="h-full dark">
             <head>
-                <title>{title}</title>
+                <title>{escape(title)}</title>
                 <link rel="icon" href="data:image/svg+xml,<svg xmlns=%22http://www.w3.org/2000/svg%22 viewBox=%220 0 100 100%22><text y=%22.9em%22 font-size=%2290%22>🎵</text></svg>">
                 <meta name="viewport" content="width=device-width, initial-scale=1.0" />
                 <link rel="stylesheet" href=style_url />
@@ -29,22 +52,30 @@ fn page_inner(component: View, before: View, title: &str) -> View {
                 hx-boost="true"
             >
 
-                <div class="sticky top-0 flex bg-inherit flex-col">
-                    <nav class="w-full text-xl px-3 py-2 bg-slate-800 flex gap-3 items-center">
-                        <a href="https://www.festorkestret.dk/">
-                            <img class="w-60 p-2" src="https://www.festorkestret.dk/wp-content/uploads/2018/06/FO-font-white.png" />
-                        </a>
-                        Setliste
-                    </nav>
-                    {before}
-                </div>
+                {if nav {
+                    html! {
+                        <div class="sticky top-0 flex bg-inherit flex-col">
+                            <nav class="w-full text-xl px-3 py-2 bg-slate-800 flex gap-3 items-center">
+                                {brand_logo(branding())}
+                                Setliste
+                            </nav>
+                            {before}
+                        </div>
 
+                        <div class="flex w-full justify-center p-4">
+                            {component}
+                        </div>
+                    }
+                } else {
+                    component
+                }}
 
-                <div class="flex w-full justify-center p-4">
-                    {component}
-                </div>
+                <div
+                    id="toasts"
+                    class="flex fixed inset-x-0 bottom-4 flex-col gap-2 items-center px-4 pointer-events-none"
+                ></div>
 
-                <script src="/assets/scripts/htmx-config.js?version=2"></script>
+                <script src="/assets/scripts/htmx-config.js?version=3"></script>
 
                 {if cfg!(debug_assertions) {
                     html! { <script src="/assets/scripts/develop-updates.js"></script> }
@@ -56,3 +87,15 @@ fn page_inner(component: View, before: View, title: &str) -> View {
         </html>
     }
 }
+
+fn brand_logo(branding: &Branding) -> View {
+    let logo = match &branding.logo_url {
+        Some(logo_url) => html! { <img class="w-60 p-2" src=logo_url alt=&branding.name /> },
+        None => html! { <span class="p-2 font-semibold">{&branding.name}</span> },
+    };
+
+    match &branding.website {
+        Some(website) => html! { <a href=website>{logo}</a> },
+        None => logo,
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{
+    app::AppState,
+    database::{Event, VoteResult},
+    errors::AppError,
+    events::event_by_id,
+    html,
+    page::fullscreen_page,
+    view::{escape, View},
+    vote_results::vote_results_fragments,
+};
+use axum::extract::{Path, State};
+use std::sync::Arc;
+
+/// How many songs fit on the projector at once.
+const PRESENTER_SONGS: usize = 8;
+
+/// The top songs as bars. Bars keep their id between updates so htmx animates their width.
+pub fn presenter_results(event: &Event, results: &[VoteResult], voters: i64) -> View {
+    let top = &results[..results.len().min(PRESENTER_SONGS)];
+    let max_points = top
+        .iter()
+        .map(|result| result.points)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let bars = top
+        .iter()
+        .enumerate()
+        .map(|(position, result)| {
+            let width = result.points * 100 / max_points;
+
+            html! {
+                <div class="flex flex-col gap-2">
+                    <div class="flex gap-4 justify-between items-baseline text-4xl">
+                        <span class="truncate">
+                            {format!("{}. {}", position + 1, escape(&result.song.title))}
+                            <span class="pl-3 text-2xl text-neutral-400">{escape(&result.song.artist)}</span>
+                        </span>
+                        <span class="font-bold">{result.points}</span>
+                    </div>
+                    <div class="w-full h-6 rounded-full bg-neutral-800">
+                        <div
+                            id=format!("presenter-bar-{}", result.song.id)
+                            class="h-6 bg-blue-500 rounded-full transition-all duration-700"
+                            style=format!("width: {}%", width)
+                        ></div>
+                    </div>
+                </div>
+            }
+        })
+        .collect::<View>();
+
+    html! {
+        <div
+            id=format!("presenter-{}", event.id)
+            hx-get=format!("/e/{}/results/presenter", event.slug)
+            hx-trigger="results-changed from:body"
+            hx-swap="outerHTML"
+            class="flex flex-col flex-1 gap-6"
+        >
+            {if event.accepts_votes() {
+                Default::default()
+            } else {
+                html! {
+                    <p class="p-4 text-4xl font-bold text-center text-white bg-red-600 rounded-lg">
+                        Afstemningen er lukket
+                    </p>
+                }
+            }}
+            {bars}
+            <p class="mt-auto text-3xl text-neutral-400">
+                <span class="text-6xl font-bold text-white">{voters}</span>
+                " har stemt"
+            </p>
+        </div>
+    }
+}
+
+/// Full-screen results for the stage projector, next to the QR code to vote.
+pub async fn presenter_page(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<View, AppError> {
+    let event = event_by_id(&state.database, id).await?;
+
+    let fragments = vote_results_fragments(&state.database, &event).await?;
+
+    let now_playing = match event.now_playing {
+        Some(song_id) => state.database.get_song(song_id).await?,
+        None => None,
+    };
+
+    Ok(fullscreen_page(
+        html! {
+            <div class="flex gap-12 p-12 w-full min-h-screen">
+                <div class="flex flex-col flex-1 gap-8">
+                    <h1 class="text-5xl font-semibold">{escape(&event.name)}</h1>
+                    <p id="now-playing" class="text-3xl text-blue-400">
+                        {now_playing
+                            .map(|song| format!("Spiller nu: {}", escape(&song.title)))
+                            .unwrap_or_default()}
+                    </p>
+                    {fragments.presenter}
+                </div>
+                <div class="flex flex-col gap-6 items-center w-1/3">
+                    <img
+                        class="p-6 w-full bg-white rounded-2xl"
+                        src=format!("/setlist/events/{}/qr.svg", event.id)
+                        alt="QR-kode til afstemningen"
+                    />
+                    <p class="text-4xl font-semibold text-center">Scan og stem på næste sang</p>
+                </div>
+            </div>
+            <script
+                src="/assets/scripts/vote-updates.js?version=10"
+                data-event=&event.slug
+                data-topics="results_changed now_playing"
+            ></script>
+        },
+        &event.name,
+    ))
+}
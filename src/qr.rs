@@ -0,0 +1,191 @@
+use crate::{
+    app::AppState,
+    database::Event,
+    errors::AppError,
+    events::{current_event, event_by_id},
+    html,
+    page::page,
+    view::{escape, View},
+};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use qrcode::{render::svg, Color, QrCode};
+use std::sync::Arc;
+
+/// Pixels per QR module in the PNG, large enough to print on a poster.
+const PNG_MODULE_SIZE: usize = 16;
+/// Modules of blank space around the code, as required by the QR spec.
+const QUIET_ZONE: usize = 4;
+
+/// The address guests open, taken from `PUBLIC_URL` or else the request's `Host` header. The
+/// proxy's `X-Forwarded-Proto` and `X-Forwarded-Host` are only used when the proxy is trusted,
+/// like `X-Forwarded-For` is.
+fn base_url(state: &AppState, headers: &HeaderMap) -> String {
+    if let Some(public_url) = &state.public_url {
+        return public_url.trim_end_matches('/').to_string();
+    }
+
+    let get = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let forwarded = |name| get(name).filter(|_| state.vote_guard.trust_forwarded_for);
+
+    let host = forwarded("X-Forwarded-Host")
+        .or_else(|| get(header::HOST.as_str()))
+        .unwrap_or("localhost:3000");
+    let scheme = match forwarded("X-Forwarded-Proto") {
+        Some("https") => "https",
+        _ => "http",
+    };
+
+    format!("{}://{}", scheme, host)
+}
+
+/// The voting link of an event. Only admins get the link with the join code in it, so a
+/// public QR code does not let people skip entering the code.
+pub fn vote_url(base_url: &str, event: &Event, with_join_code: bool) -> String {
+    match &event.join_code {
+        Some(code) if with_join_code => format!(
+            "{}/e/{}/join?code={}",
+            base_url,
+            event.slug,
+            percent_encode(code)
+        ),
+        _ => format!("{}/e/{}/vote", base_url, event.slug),
+    }
+}
+
+/// Encodes everything but the characters URLs leave unreserved.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn qr_svg(url: &str) -> Result<Response, AppError> {
+    let code = QrCode::new(url).map_err(anyhow::Error::from)?;
+    let image = code
+        .render::<svg::Color>()
+        .min_dimensions(256, 256)
+        .quiet_zone(true)
+        .build();
+
+    Ok(([(header::CONTENT_TYPE, "image/svg+xml")], image).into_response())
+}
+
+fn qr_png(url: &str) -> Result<Response, AppError> {
+    let code = QrCode::new(url).map_err(anyhow::Error::from)?;
+    let modules = code.width();
+    let colors = code.to_colors();
+
+    let size = (modules + 2 * QUIET_ZONE) * PNG_MODULE_SIZE;
+    let mut pixels = vec![u8::MAX; size * size];
+
+    for y in 0..size {
+        for x in 0..size {
+            let module_x = (x / PNG_MODULE_SIZE).checked_sub(QUIET_ZONE);
+            let module_y = (y / PNG_MODULE_SIZE).checked_sub(QUIET_ZONE);
+
+            if let (Some(module_x), Some(module_y)) = (module_x, module_y) {
+                if module_x < modules
+                    && module_y < modules
+                    && colors[module_y * modules + module_x] == Color::Dark
+                {
+                    pixels[y * size + x] = 0;
+                }
+            }
+        }
+    }
+
+    let mut image = Vec::new();
+    let mut encoder = png::Encoder::new(&mut image, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(anyhow::Error::from)?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(anyhow::Error::from)?;
+    writer.finish().map_err(anyhow::Error::from)?;
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], image).into_response())
+}
+
+pub async fn current_qr_svg(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let event = current_event(&state.database).await?;
+
+    qr_svg(&vote_url(&base_url(&state, &headers), &event, false))
+}
+
+pub async fn current_qr_png(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let event = current_event(&state.database).await?;
+
+    qr_png(&vote_url(&base_url(&state, &headers), &event, false))
+}
+
+pub async fn event_qr_svg(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let event = event_by_id(&state.database, id).await?;
+
+    qr_svg(&vote_url(&base_url(&state, &headers), &event, true))
+}
+
+pub async fn event_qr_png(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let event = event_by_id(&state.database, id).await?;
+
+    qr_png(&vote_url(&base_url(&state, &headers), &event, true))
+}
+
+/// Shows the QR code big enough to project on the screen behind the band.
+pub async fn qr_page(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<View, AppError> {
+    let event = event_by_id(&state.database, id).await?;
+    let url = vote_url(&base_url(&state, &headers), &event, true);
+
+    let qr_container = html! {
+        <div class="flex flex-col gap-4 items-center w-full">
+            <a class="self-start text-blue-500" href=format!("/setlist/events/{}", event.id)>
+                {escape(&event.name)}
+            </a>
+            <h1 class="text-4xl font-semibold text-center">Stem på næste sang</h1>
+            <img
+                class="p-4 w-full max-w-3xl bg-white rounded-lg"
+                src=format!("/setlist/events/{}/qr.svg", event.id)
+                alt=escape(&url)
+            />
+            <p class="text-2xl text-center break-all">{escape(&url)}</p>
+            <a
+                class="text-blue-500"
+                href=format!("/setlist/events/{}/qr.png", event.id)
+                download=format!("{}.png", event.slug)
+            >
+                Hent som PNG til plakater
+            </a>
+        </div>
+    };
+
+    Ok(page(qr_container, &event.name))
+}
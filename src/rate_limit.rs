@@ -0,0 +1,368 @@
+use crate::{
+    api::ApiError,
+    app::AppState,
+    database::{Event, RejectionReason},
+    errors::{AppError, BadRequestError},
+    events::event_by_slug,
+    session::Session,
+};
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, OriginalUri, Path, Request, State},
+    http::{request::Parts, Extensions, HeaderMap, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+/// Counts hits per key in fixed windows.
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    hits: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> RateLimiter {
+        RateLimiter {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a hit for `key`, returning false when the key has used up its limit.
+    pub fn check(&self, key: &str) -> bool {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+
+        // forget keys that have been quiet for a whole window
+        if hits.len() > 10_000 {
+            hits.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+
+        let (start, count) = hits.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(*start) >= self.window {
+            *start = now;
+            *count = 0;
+        }
+
+        *count += 1;
+        *count <= self.limit
+    }
+}
+
+/// How often the rejected votes counted in memory are saved.
+const SAVE_REJECTED_VOTES_EVERY: Duration = Duration::from_secs(60);
+
+/// Who was turned away from voting at an event, and why.
+#[derive(PartialEq, Eq, Hash, Debug)]
+pub struct Rejection {
+    pub event_id: i32,
+    pub session_id: String,
+    pub ip: Option<String>,
+    pub reason: RejectionReason,
+}
+
+/// Counts rejected votes in memory, so a guest hammering the vote button costs one insert per
+/// minute instead of one per click.
+#[derive(Default)]
+pub struct RejectionCounts {
+    counts: Mutex<HashMap<Rejection, i32>>,
+}
+
+impl RejectionCounts {
+    pub fn count(&self, rejection: Rejection) {
+        *self.counts.lock().unwrap().entry(rejection).or_insert(0) += 1;
+    }
+
+    /// Takes the counts since the last call.
+    pub fn take(&self) -> HashMap<Rejection, i32> {
+        std::mem::take(&mut *self.counts.lock().unwrap())
+    }
+}
+
+/// The safeguards against a single guest stuffing the ballot.
+pub struct VoteGuard {
+    pub per_ip: RateLimiter,
+    pub per_session: RateLimiter,
+    pub rejected: RejectionCounts,
+    /// Use the first address in `X-Forwarded-For`, for when the app runs behind a proxy. QR codes
+    /// then also link to the scheme and host in `X-Forwarded-Proto` and `X-Forwarded-Host`.
+    pub trust_forwarded_for: bool,
+}
+
+impl VoteGuard {
+    pub fn client_ip(&self, request: &Request) -> Option<String> {
+        self.ip_from(request.headers(), request.extensions())
+    }
+
+    fn ip_from(&self, headers: &HeaderMap, extensions: &Extensions) -> Option<String> {
+        let forwarded_for = headers
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty());
+
+        match forwarded_for {
+            Some(ip) if self.trust_forwarded_for => Some(ip),
+            _ => extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip().to_string()),
+        }
+    }
+}
+
+/// The IP address of a request, as `VoteGuard::client_ip` finds it.
+pub struct ClientIp(pub Option<String>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(ClientIp(
+            state.vote_guard.ip_from(&parts.headers, &parts.extensions),
+        ))
+    }
+}
+
+/// Rate limits votes per IP address and session, and caps how many sessions from one IP
+/// address can vote at an event.
+pub async fn limit_votes(
+    State(state): State<Arc<AppState>>,
+    Path((slug, _song_id)): Path<(String, i32)>,
+    session: Session,
+    OriginalUri(uri): OriginalUri,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let ip = state.vote_guard.client_ip(&request);
+
+    let checked = match event_by_slug(&state.database, &slug).await {
+        Ok(event) => check_vote(&state, &event, &session, ip.as_deref(), request.method()).await,
+        Err(err) => Err(err),
+    };
+
+    match checked {
+        Ok(()) => Ok(next.run(request).await),
+        Err(err) if uri.path().starts_with("/api/") => Ok(ApiError::from(err).into_response()),
+        Err(err) => Err(err),
+    }
+}
+
+async fn check_vote(
+    state: &AppState,
+    event: &Event,
+    session: &Session,
+    ip: Option<&str>,
+    method: &Method,
+) -> Result<(), AppError> {
+    let guard = &state.vote_guard;
+
+    let reason = match rate_limited(guard, session, ip) {
+        Some(reason) => Some(reason),
+        None if *method == Method::POST => too_many_sessions(state, event, session, ip).await?,
+        None => None,
+    };
+
+    let Some(reason) = reason else {
+        return Ok(());
+    };
+
+    reject(guard, event, session, ip, reason);
+
+    match reason {
+        RejectionReason::SessionsPerIp => Err(BadRequestError::TooManySessions.into()),
+        _ => Err(AppError::RateLimited),
+    }
+}
+
+/// Rate limits join code attempts together with votes, so codes cannot be guessed from a
+/// script.
+pub fn limit_join_attempts(
+    guard: &VoteGuard,
+    event: &Event,
+    session: &Session,
+    ip: Option<&str>,
+) -> Result<(), AppError> {
+    match rate_limited(guard, session, ip) {
+        Some(reason) => {
+            reject(guard, event, session, ip, reason);
+            Err(AppError::RateLimited)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Counts a request against the limits per IP address and per session.
+fn rate_limited(guard: &VoteGuard, session: &Session, ip: Option<&str>) -> Option<RejectionReason> {
+    if ip.is_some_and(|ip| !guard.per_ip.check(ip)) {
+        Some(RejectionReason::IpRateLimit)
+    } else if !guard.per_session.check(&session.id) {
+        Some(RejectionReason::SessionRateLimit)
+    } else {
+        None
+    }
+}
+
+fn reject(
+    guard: &VoteGuard,
+    event: &Event,
+    session: &Session,
+    ip: Option<&str>,
+    reason: RejectionReason,
+) {
+    debug!(
+        "Rejected request by {} from {:?} at {}: {}",
+        session.id,
+        ip,
+        event.slug,
+        reason.as_str()
+    );
+    guard.rejected.count(Rejection {
+        event_id: event.id,
+        session_id: session.id.clone(),
+        ip: ip.map(str::to_string),
+        reason,
+    });
+}
+
+/// Only sessions that have not voted yet count towards the cap, so guests already voting
+/// can keep changing their votes.
+async fn too_many_sessions(
+    state: &AppState,
+    event: &Event,
+    session: &Session,
+    ip: Option<&str>,
+) -> Result<Option<RejectionReason>, AppError> {
+    let (Some(cap), Some(ip)) = (event.max_sessions_per_ip, ip) else {
+        return Ok(None);
+    };
+
+    if state.database.count_votes(event.id, &session.id).await? > 0 {
+        return Ok(None);
+    }
+
+    let sessions = state
+        .database
+        .count_ip_sessions(event.id, ip, &session.id)
+        .await?;
+
+    Ok((sessions >= i64::from(cap)).then_some(RejectionReason::SessionsPerIp))
+}
+
+/// Writes the rejected votes counted since the last save, one row per session and reason.
+pub async fn save_rejected_votes(state: &AppState) -> Result<(), AppError> {
+    for (rejection, count) in state.vote_guard.rejected.take() {
+        warn!(
+            "Rejected {} votes by {} from {:?} at event {}: {}",
+            count,
+            rejection.session_id,
+            rejection.ip,
+            rejection.event_id,
+            rejection.reason.as_str()
+        );
+        state
+            .database
+            .record_rejected_vote(
+                rejection.event_id,
+                &rejection.session_id,
+                rejection.ip.as_deref(),
+                rejection.reason,
+                count,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Saves the rejected votes every minute.
+pub async fn keep_saving_rejected_votes(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(SAVE_REJECTED_VOTES_EVERY);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = save_rejected_votes(&state).await {
+            warn!("Failed to save rejected votes: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use std::thread::sleep;
+
+    #[test]
+    fn keys_are_limited_separately() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+
+        assert!(limiter.check("10.0.0.1"));
+        assert!(limiter.check("10.0.0.1"));
+        assert!(!limiter.check("10.0.0.1"));
+        assert!(limiter.check("10.0.0.2"));
+    }
+
+    #[test]
+    fn the_limit_starts_over_in_the_next_window() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(50));
+
+        assert!(limiter.check("guest"));
+        assert!(!limiter.check("guest"));
+
+        sleep(Duration::from_millis(60));
+        assert!(limiter.check("guest"));
+        assert!(!limiter.check("guest"));
+    }
+
+    #[test]
+    fn rejections_are_counted_until_they_are_taken() {
+        let counts = RejectionCounts::default();
+        let rejection = || Rejection {
+            event_id: 1,
+            session_id: "guest".to_string(),
+            ip: Some("10.0.0.1".to_string()),
+            reason: RejectionReason::SessionRateLimit,
+        };
+
+        counts.count(rejection());
+        counts.count(rejection());
+
+        assert_eq!(counts.take().get(&rejection()), Some(&2));
+        assert!(counts.take().is_empty());
+    }
+
+    #[test]
+    fn forwarded_addresses_are_only_used_when_trusted() {
+        let mut guard = VoteGuard {
+            per_ip: RateLimiter::new(1, Duration::from_secs(60)),
+            per_session: RateLimiter::new(1, Duration::from_secs(60)),
+            rejected: RejectionCounts::default(),
+            trust_forwarded_for: false,
+        };
+        let request = Request::builder()
+            .header("X-Forwarded-For", "203.0.113.7, 10.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(guard.client_ip(&request), None);
+
+        guard.trust_forwarded_for = true;
+        assert_eq!(guard.client_ip(&request).as_deref(), Some("203.0.113.7"));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{api::ApiError, app::AppState, errors::AppError};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, OriginalUri, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar, SignedCookieJar,
+};
+use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+const SESSION_COOKIE: &str = "session_id";
+
+/// The guest behind a request, known from the signed `session_id` cookie.
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub id: String,
+    /// The session was issued for this request, so the client did not send a valid one.
+    pub is_new: bool,
+}
+
+impl Session {
+    /// Rejects sessions the client did not already have, for requests that act on its votes.
+    pub fn existing(&self) -> Result<&str, AppError> {
+        match self.is_new {
+            true => Err(AppError::Unauthorized),
+            false => Ok(&self.id),
+        }
+    }
+}
+
+/// Finds the session of a request from its signed cookie, without issuing one.
+async fn find_session(
+    state: &AppState,
+    jar: &SignedCookieJar,
+) -> Result<Option<Session>, AppError> {
+    let Some(cookie) = jar.get(SESSION_COOKIE) else {
+        return Ok(None);
+    };
+
+    Ok(state
+        .database
+        .get_session(cookie.value())
+        .await?
+        .map(|session| Session {
+            id: session.id,
+            is_new: false,
+        }))
+}
+
+/// Takes the session `remember_me` issued, or else the one the client already has. Requests
+/// without a session are turned away, as JSON under `/api`.
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Session {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(session) = parts.extensions.get::<Session>() {
+            return Ok(session.clone());
+        }
+
+        let jar = SignedCookieJar::from_headers(&parts.headers, state.key.clone());
+        let session = find_session(state, &jar)
+            .await
+            .and_then(|session| session.ok_or(AppError::Unauthorized));
+
+        session.map_err(|err| {
+            let is_api = parts
+                .extensions
+                .get::<OriginalUri>()
+                .is_some_and(|uri| uri.path().starts_with("/api/"));
+
+            match is_api {
+                true => ApiError::from(err).into_response(),
+                false => err.into_response(),
+            }
+        })
+    }
+}
+
+pub(crate) fn generate_session_id() -> String {
+    OsRng
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// Issues a server-side session to guests that do not have a valid one. Only the guest pages
+/// and `POST /api/v1/session` use it, so health checks and crawlers do not fill up the
+/// sessions table.
+pub async fn remember_me(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<(SignedCookieJar, Response), AppError> {
+    let mut jar = SignedCookieJar::from_headers(request.headers(), state.key.clone());
+
+    let session = match find_session(&state, &jar).await? {
+        Some(session) => session,
+        None => {
+            if CookieJar::from_headers(request.headers())
+                .get(SESSION_COOKIE)
+                .is_some()
+            {
+                warn!("Replacing unknown or tampered session cookie");
+            }
+
+            let ip = state.vote_guard.client_ip(&request);
+            let session = state
+                .database
+                .create_session(&generate_session_id(), ip.as_deref())
+                .await?;
+            info!("New session {}", session.id);
+
+            let cookie = Cookie::build((SESSION_COOKIE, session.id.clone()))
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Strict);
+            jar = jar.add(cookie);
+
+            Session {
+                id: session.id,
+                is_new: true,
+            }
+        }
+    };
+
+    request.extensions_mut().insert(session);
+
+    Ok((jar, next.run(request).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_ids_are_long_and_random() {
+        let id = generate_session_id();
+
+        assert_eq!(id.len(), 32);
+        assert!(id.chars().all(|char| char.is_ascii_alphanumeric()));
+        assert_ne!(id, generate_session_id());
+    }
+
+    #[test]
+    fn only_existing_sessions_can_act_on_votes() {
+        let existing = Session {
+            id: "guest".to_string(),
+            is_new: false,
+        };
+        assert_eq!(existing.existing().unwrap(), "guest");
+
+        let new = Session {
+            is_new: true,
+            ..existing
+        };
+        assert!(matches!(new.existing(), Err(AppError::Unauthorized)));
+    }
+}
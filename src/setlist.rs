@@ -1,13 +1,21 @@
 use crate::{
-    app::AppState, database::Song, html, icons, page::page, view::View, vote_results::votes_updated,
+    app::AppState,
+    database::{NewSong, Song},
+    errors::AppError,
+    html, icons,
+    import::{find_duplicates, parse_songs, Duplicate, ImportFormat, ImportRow},
+    page::page,
+    updates::{publish, Update},
+    view::{escape, View},
+    vote_results::song_visibility_changed,
 };
 use axum::{
-    extract::{Path, State},
+    extract::{Multipart, Path, State},
     Form,
 };
 use serde::Deserialize;
 use std::sync::Arc;
-use tracing::warn;
+use tracing::{info, warn};
 
 #[derive(Deserialize, Debug)]
 pub struct CreateSongInput {
@@ -19,7 +27,13 @@ pub struct CreateSongInput {
 pub async fn add_song(
     State(state): State<Arc<AppState>>,
     Form(input): Form<CreateSongInput>,
-) -> View {
+) -> Result<View, AppError> {
+    if input.title.trim().is_empty() || input.artist.trim().is_empty() {
+        return Err(AppError::Validation(
+            "Sangen skal have en titel og en kunstner".to_string(),
+        ));
+    }
+
     let description = match input.description.is_empty() {
         true => None,
         false => Some(input.description.as_str()),
@@ -28,29 +42,176 @@ pub async fn add_song(
     let song = state
         .database
         .add_song(&input.artist, &input.title, description)
-        .await
-        .unwrap();
+        .await?;
 
-    song_card(song)
+    Ok(song_card(song))
 }
 
-pub async fn delete_song(Path(id): Path<i32>, State(state): State<Arc<AppState>>) {
+pub async fn delete_song(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(), AppError> {
     warn!("Deleting song {}", id);
-    state.database.delete_song(id).await.unwrap();
+    state.database.delete_song(id).await?;
+
+    publish(&state.tx, Update::SongDeleted { song_id: id });
+
+    Ok(())
 }
 
-pub async fn hide_song(Path(id): Path<i32>, State(state): State<Arc<AppState>>) -> View {
+pub async fn hide_song(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<View, AppError> {
     warn!("Hiding song {}", id);
-    let song = state.database.hide_song(id).await.unwrap();
+    let song = state.database.hide_song(id).await?;
+
+    publish(&state.tx, Update::SongHidden { song_id: song.id });
+    song_visibility_changed(&state.tx, &state.database, song.id).await;
 
-    song_card(song)
+    Ok(song_card(song))
 }
 
-pub async fn unhide_song(Path(id): Path<i32>, State(state): State<Arc<AppState>>) -> View {
+pub async fn unhide_song(
+    Path(id): Path<i32>,
+    State(state): State<Arc<AppState>>,
+) -> Result<View, AppError> {
     warn!("Unhiding song {}", id);
-    let song = state.database.unhide_song(id).await.unwrap();
+    let song = state.database.unhide_song(id).await?;
+
+    publish(&state.tx, Update::SongUnhidden { song: song.clone() });
+    song_visibility_changed(&state.tx, &state.database, song.id).await;
+
+    Ok(song_card(song))
+}
+
+/// Reads an uploaded repertoire and shows its songs, so nothing is added before the admin
+/// has looked them over.
+pub async fn preview_import(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<View, AppError> {
+    let upload_failed = |_| AppError::Validation("Filen kunne ikke uploades".to_string());
 
-    song_card(song)
+    let mut file = None;
+    while let Some(field) = multipart.next_field().await.map_err(upload_failed)? {
+        if field.name() == Some("file") {
+            let name = field.file_name().unwrap_or_default().to_string();
+            let contents = field.text().await.map_err(upload_failed)?;
+            file = Some((name, contents));
+        }
+    }
+
+    let (name, contents) = file
+        .filter(|(_, contents)| !contents.trim().is_empty())
+        .ok_or_else(|| AppError::Validation("Vælg en fil med sange".to_string()))?;
+
+    let songs = parse_songs(ImportFormat::from_file_name(&name), &contents)?;
+    let repertoire = state.database.get_setlist().await?;
+
+    Ok(import_preview(find_duplicates(songs, &repertoire)))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportSongsInput {
+    /// Every song in the preview as JSON.
+    songs: String,
+    /// The positions in `songs` of the ones picked for import.
+    #[serde(default)]
+    row: Vec<usize>,
+}
+
+pub async fn import_songs(
+    State(state): State<Arc<AppState>>,
+    axum_extra::extract::Form(input): axum_extra::extract::Form<ImportSongsInput>,
+) -> Result<View, AppError> {
+    let songs: Vec<NewSong> = serde_json::from_str(&input.songs).map_err(|_| {
+        AppError::Validation("Importen kunne ikke læses, upload filen igen".to_string())
+    })?;
+
+    let picked: Vec<NewSong> = input
+        .row
+        .iter()
+        .filter_map(|&row| songs.get(row).cloned())
+        .collect();
+
+    if picked.is_empty() {
+        return Err(AppError::Validation(
+            "Vælg mindst én sang at importere".to_string(),
+        ));
+    }
+
+    info!("Importing {} songs", picked.len());
+    let added = state.database.add_songs(&picked).await?;
+    let count = added.len();
+
+    let cards = added.into_iter().map(song_card).collect::<View>();
+
+    Ok(html! {
+        {cards}
+        <div id="import-preview" hx-swap-oob="true">
+            <p class="text-green-500">{format!("Importerede {} sange", count)}</p>
+        </div>
+    })
+}
+
+fn import_preview(rows: Vec<ImportRow>) -> View {
+    let songs: Vec<&NewSong> = rows.iter().map(|row| &row.song).collect();
+    let songs = escape(&serde_json::to_string(&songs).unwrap_or_default());
+    let new_songs = rows.iter().filter(|row| row.duplicate.is_none()).count();
+    let total = rows.len();
+
+    let rows = rows
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| {
+            html! {
+                <label class=format!(
+                    "flex gap-3 items-center p-2 rounded border dark:border-neutral-700 cursor-pointer {}",
+                    if row.duplicate.is_some() { "text-neutral-500" } else { Default::default() },
+                )>
+                    <input
+                        type="checkbox"
+                        name="row"
+                        value=index.to_string()
+                        {if row.duplicate.is_none() { "checked" } else { Default::default() }}
+                    />
+                    <div class="flex flex-col">
+                        <span class="font-semibold">{escape(&row.song.title)}</span>
+                        <span class="text-sm text-neutral-500">{escape(&row.song.artist)}</span>
+                        {match row.duplicate {
+                            Some(Duplicate::Existing) => html! {
+                                <span class="text-sm text-yellow-500">{"Findes allerede i repertoiret"}</span>
+                            },
+                            Some(Duplicate::InFile) => html! {
+                                <span class="text-sm text-yellow-500">{"Står flere gange i filen"}</span>
+                            },
+                            None => Default::default(),
+                        }}
+                    </div>
+                </label>
+            }
+        })
+        .collect::<View>();
+
+    html! {
+        <form
+            id="import-preview"
+            hx-post="/setlist/import"
+            hx-target="#add-song"
+            hx-swap="beforebegin"
+            class="flex flex-col gap-2"
+        >
+            <input type="hidden" name="songs" value=songs />
+            <p>{format!("{} sange i filen, {} er nye", total, new_songs)}</p>
+            {rows}
+            <input
+                class="p-1 text-white bg-blue-500 rounded transition-colors cursor-pointer hover:bg-blue-400"
+                type="submit"
+                value="Importér de valgte sange"
+            />
+        </form>
+    }
 }
 
 fn song_card(song: Song) -> View {
@@ -63,7 +224,7 @@ fn song_card(song: Song) -> View {
             )
         >
             <div class="flex flex-wrap gap-2 justify-between items-center">
-                <h2 class="text-2xl font-semibold whitespace-nowrap">{song.title}</h2>
+                <h2 class="text-2xl font-semibold whitespace-nowrap">{escape(&song.title)}</h2>
 
                 <div class="flex gap-4">
                     {if song.hidden {
@@ -99,9 +260,9 @@ fn song_card(song: Song) -> View {
                     </button>
                 </div>
             </div>
-            <h4 class="text-sm">{song.artist}</h4>
+            <h4 class="text-sm">{escape(&song.artist)}</h4>
             {if let Some(description) = song.description {
-                html! { <p class="pt-2">{description}</p> }
+                html! { <p class="pt-2">{escape(&description)}</p> }
             } else {
                 Default::default()
             }}
@@ -109,32 +270,25 @@ fn song_card(song: Song) -> View {
     }
 }
 
-pub async fn clear_votes(State(state): State<Arc<AppState>>) {
-    state.database.clear_votes().await.unwrap();
-
-    votes_updated(&state.tx, &state.database).await;
-}
-
-pub async fn setlist_page(State(state): State<Arc<AppState>>) -> View {
+pub async fn setlist_page(State(state): State<Arc<AppState>>) -> Result<View, AppError> {
     let songs = state
         .database
         .get_setlist()
-        .await
-        .unwrap()
+        .await?
         .into_iter()
         .map(song_card)
         .collect::<View>();
 
     let song_container = html! {
         <div class="flex flex-col gap-3 w-full max-w-lg">
-            <button
-                hx-post="/setlist/votes/clear"
-                hx-swap="none"
-                class="flex gap-3 justify-center p-4 max-w-lg rounded-lg border shadow transition-colors dark:border-neutral-700 dark:bg-neutral-950"
-            >
-                <p>Slet alle stemmer</p>
-                <p class="text-red-500">{icons::trash_2()}</p>
-            </button>
+            <div class="flex justify-between">
+                <a class="text-blue-500" href="/setlist/events">
+                    Arrangementer
+                </a>
+                <form method="post" action="/logout">
+                    <input class="cursor-pointer text-neutral-500 hover:text-white" type="submit" value="Log ud" />
+                </form>
+            </div>
             {songs}
             <details id="add-song">
                 <form
@@ -178,8 +332,38 @@ pub async fn setlist_page(State(state): State<Arc<AppState>>) -> View {
                     {icons::plus_circle()}
                 </summary>
             </details>
+            <details id="import-songs">
+                <div class="flex flex-col gap-3 p-4 mt-3 max-w-lg rounded-lg border shadow dark:border-neutral-700 dark:bg-neutral-950">
+                    <form
+                        hx-post="/setlist/import/preview"
+                        hx-encoding="multipart/form-data"
+                        hx-target="#import-preview"
+                        hx-swap="outerHTML"
+                        class="flex flex-col gap-3 text-neutral-500"
+                    >
+                        <label class="dark:text-white" for="file">
+                            {"CSV (titel, kunstner, beskrivelse), JSON, M3U eller en linje per sang:"}
+                        </label>
+                        <input
+                            type="file"
+                            id="file"
+                            name="file"
+                            accept=".csv,.json,.m3u,.m3u8,.txt"
+                        />
+                        <input
+                            class="p-1 text-white bg-blue-500 rounded transition-colors cursor-pointer hover:bg-blue-400"
+                            type="submit"
+                            value="Vis sangene"
+                        />
+                    </form>
+                    <div id="import-preview"></div>
+                </div>
+                <summary class="p-4 max-w-lg text-center rounded-lg border shadow transition-colors cursor-pointer hover:text-white text-neutral-500 dark:border-neutral-700 dark:bg-neutral-950">
+                    {"Importér sange fra en fil"}
+                </summary>
+            </details>
         </div>
     };
 
-    page(song_container, "Setlist")
+    Ok(page(song_container, "Setlist"))
 }
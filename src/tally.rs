@@ -0,0 +1,249 @@
+use crate::database::{Event, Song, Vote, VoteResult, VotingMode};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+};
+
+/// Scores the songs of an event according to its voting mode, best song first.
+pub fn tally(event: &Event, songs: Vec<Song>, votes: &[Vote]) -> Vec<VoteResult> {
+    let mut vote_counts: HashMap<i32, i64> = HashMap::new();
+    for vote in votes {
+        *vote_counts.entry(vote.song_id).or_default() += 1;
+    }
+
+    let mut result: Vec<_> = songs
+        .into_iter()
+        .map(|song| VoteResult {
+            vote_count: vote_counts.get(&song.id).copied().unwrap_or(0),
+            points: 0,
+            song,
+        })
+        .collect();
+
+    match event.voting_mode {
+        VotingMode::Approval => {
+            for result in &mut result {
+                result.points = result.vote_count;
+            }
+            result.sort_by_key(|result| Reverse(result.points));
+        }
+        VotingMode::Borda => {
+            let points = borda(event.vote_budget, votes);
+            for result in &mut result {
+                result.points = points.get(&result.song.id).copied().unwrap_or(0);
+            }
+            result.sort_by_key(|result| Reverse(result.points));
+        }
+        VotingMode::InstantRunoff => {
+            let eliminated = instant_runoff(&result, votes);
+            let position: HashMap<i32, usize> = eliminated
+                .iter()
+                .enumerate()
+                .map(|(position, (song_id, _))| (*song_id, position))
+                .collect();
+            let points: HashMap<i32, i64> = eliminated.into_iter().collect();
+
+            for result in &mut result {
+                result.points = points.get(&result.song.id).copied().unwrap_or(0);
+            }
+            result.sort_by_key(|result| Reverse(position.get(&result.song.id).copied()));
+        }
+    }
+
+    result
+}
+
+/// A guest's first pick is worth `vote_budget` points, the second one less and so on.
+fn borda(vote_budget: i32, votes: &[Vote]) -> HashMap<i32, i64> {
+    let mut points: HashMap<i32, i64> = HashMap::new();
+
+    for vote in votes {
+        let score = (vote_budget - vote.rank + 1).max(1);
+        *points.entry(vote.song_id).or_default() += i64::from(score);
+    }
+
+    points
+}
+
+/// Eliminates the song with the fewest first preferences until none are left, returning
+/// the songs in the order they were eliminated together with the first preferences they
+/// held in that round.
+fn instant_runoff(results: &[VoteResult], votes: &[Vote]) -> Vec<(i32, i64)> {
+    let ballots = ballots(votes);
+    let mut remaining: Vec<&VoteResult> = results.iter().collect();
+    let mut in_race: HashSet<i32> = results.iter().map(|result| result.song.id).collect();
+    let mut eliminated = Vec::with_capacity(results.len());
+
+    // each ballot points at its highest ranked song still in the race, and only the ballots
+    // of an eliminated song move on to their next pick
+    let mut current = vec![0; ballots.len()];
+    let mut holders: HashMap<i32, Vec<usize>> = HashMap::new();
+    for (index, ballot) in ballots.iter().enumerate() {
+        move_to_next_pick(index, ballot, &in_race, &mut current, &mut holders);
+    }
+
+    while !remaining.is_empty() {
+        let first_preferences = |song_id: i32| {
+            holders
+                .get(&song_id)
+                .map_or(0, |ballots| ballots.len() as i64)
+        };
+
+        // ties go out with the fewest votes overall, then the newest song
+        let (index, loser) = remaining
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, result)| {
+                (
+                    first_preferences(result.song.id),
+                    result.vote_count,
+                    Reverse(result.song.id),
+                )
+            })
+            .expect("remaining songs is not empty");
+
+        let song_id = loser.song.id;
+        eliminated.push((song_id, first_preferences(song_id)));
+        remaining.remove(index);
+        in_race.remove(&song_id);
+
+        for ballot in holders.remove(&song_id).unwrap_or_default() {
+            move_to_next_pick(
+                ballot,
+                &ballots[ballot],
+                &in_race,
+                &mut current,
+                &mut holders,
+            );
+        }
+    }
+
+    eliminated
+}
+
+/// Moves a ballot down to its next pick that is still in the race, if it has one.
+fn move_to_next_pick(
+    index: usize,
+    ballot: &[i32],
+    in_race: &HashSet<i32>,
+    current: &mut [usize],
+    holders: &mut HashMap<i32, Vec<usize>>,
+) {
+    while let Some(song_id) = ballot.get(current[index]) {
+        if in_race.contains(song_id) {
+            holders.entry(*song_id).or_default().push(index);
+            return;
+        }
+        current[index] += 1;
+    }
+}
+
+/// Each guest's picks, ordered by rank.
+fn ballots(votes: &[Vote]) -> Vec<Vec<i32>> {
+    let mut ballots: HashMap<&str, Vec<&Vote>> = HashMap::new();
+    for vote in votes {
+        ballots.entry(&vote.session_id).or_default().push(vote);
+    }
+
+    ballots
+        .into_values()
+        .map(|mut ballot| {
+            ballot.sort_by_key(|vote| vote.rank);
+            ballot.iter().map(|vote| vote.song_id).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(voting_mode: VotingMode) -> Event {
+        Event {
+            vote_budget: 3,
+            voting_mode,
+            ..Event::for_tests()
+        }
+    }
+
+    fn songs(count: i32) -> Vec<Song> {
+        (1..=count)
+            .map(|id| Song {
+                id,
+                artist: "Festorkestret".to_string(),
+                title: format!("Sang {}", id),
+                description: None,
+                hidden: false,
+            })
+            .collect()
+    }
+
+    /// One vote per pick, ranked in the order given, for each ballot.
+    fn votes(ballots: &[&[i32]]) -> Vec<Vote> {
+        let mut votes = Vec::new();
+        for (guest, ballot) in ballots.iter().enumerate() {
+            for (rank, song_id) in ballot.iter().enumerate() {
+                votes.push(Vote {
+                    id: votes.len() as i32,
+                    session_id: format!("guest-{}", guest),
+                    song_id: *song_id,
+                    event_id: 1,
+                    rank: rank as i32 + 1,
+                });
+            }
+        }
+        votes
+    }
+
+    fn placings(results: &[VoteResult]) -> Vec<(i32, i64)> {
+        results
+            .iter()
+            .map(|result| (result.song.id, result.points))
+            .collect()
+    }
+
+    #[test]
+    fn approval_gives_a_point_per_vote() {
+        let votes = votes(&[&[1, 2], &[2], &[2, 3]]);
+        let results = tally(&event(VotingMode::Approval), songs(3), &votes);
+
+        assert_eq!(placings(&results), vec![(2, 3), (1, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn borda_gives_the_first_pick_the_most_points() {
+        let votes = votes(&[&[1, 2, 3], &[3]]);
+        let results = tally(&event(VotingMode::Borda), songs(3), &votes);
+
+        assert_eq!(placings(&results), vec![(3, 4), (1, 3), (2, 2)]);
+    }
+
+    #[test]
+    fn instant_runoff_moves_ballots_of_eliminated_songs_to_their_next_pick() {
+        let votes = votes(&[&[1], &[1], &[1], &[1], &[2], &[2], &[2], &[3, 2], &[3, 2]]);
+        let results = tally(&event(VotingMode::InstantRunoff), songs(3), &votes);
+
+        // song 1 leads on first picks, but song 3's ballots carry song 2 past it
+        assert_eq!(placings(&results), vec![(2, 5), (1, 4), (3, 2)]);
+    }
+
+    #[test]
+    fn instant_runoff_ties_go_out_with_the_fewest_votes_then_the_newest_song() {
+        let votes = votes(&[&[1, 2], &[2], &[3]]);
+        let results = tally(&event(VotingMode::InstantRunoff), songs(3), &votes);
+
+        // all three start with one first pick, song 2 has the most votes overall and song 3
+        // is newer than song 1
+        assert_eq!(placings(&results), vec![(2, 2), (1, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn instant_runoff_skips_picks_that_are_not_on_the_setlist() {
+        let votes = votes(&[&[4, 1], &[2]]);
+        let results = tally(&event(VotingMode::InstantRunoff), songs(2), &votes);
+
+        // song 4 is not among the songs, so the ballot counts for song 1 and the newer song 2
+        // goes out on the tie
+        assert_eq!(placings(&results), vec![(1, 1), (2, 1)]);
+    }
+}
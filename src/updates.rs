@@ -0,0 +1,243 @@
+use crate::{
+    app::AppState,
+    database::{Song, VoteResult},
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::{
+        sse::{self, KeepAlive, Sse},
+        IntoResponse,
+    },
+};
+use axum_extra::extract::Query;
+use futures::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, convert::Infallible, sync::Arc};
+use tokio::sync::broadcast::{error::RecvError, Receiver, Sender};
+use tracing::{debug, info, warn};
+
+/// Something that happened which clients may want to react to, sent over `/websocket` as
+/// JSON tagged with its `kind`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Update {
+    /// The votes or the songs that can be voted on changed. Pages fetch the views of the
+    /// results they show again.
+    ResultsChanged {
+        event: String,
+        results: Vec<VoteResult>,
+        voters: i64,
+    },
+    SongHidden {
+        song_id: i32,
+    },
+    SongUnhidden {
+        song: Song,
+    },
+    SongDeleted {
+        song_id: i32,
+    },
+    /// Songs were added to or removed from the setlist of an event.
+    SetlistChanged {
+        event: String,
+    },
+    VotingClosed {
+        event: String,
+    },
+    /// The song the band is playing, or `None` between songs.
+    NowPlaying {
+        event: String,
+        song: Option<Song>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    ResultsChanged,
+    SongHidden,
+    SongUnhidden,
+    SongDeleted,
+    SetlistChanged,
+    VotingClosed,
+    NowPlaying,
+}
+
+impl Update {
+    pub fn topic(&self) -> Topic {
+        match self {
+            Update::ResultsChanged { .. } => Topic::ResultsChanged,
+            Update::SongHidden { .. } => Topic::SongHidden,
+            Update::SongUnhidden { .. } => Topic::SongUnhidden,
+            Update::SongDeleted { .. } => Topic::SongDeleted,
+            Update::SetlistChanged { .. } => Topic::SetlistChanged,
+            Update::VotingClosed { .. } => Topic::VotingClosed,
+            Update::NowPlaying { .. } => Topic::NowPlaying,
+        }
+    }
+
+    /// The slug of the event the update is about. Songs belong to every event.
+    pub fn event(&self) -> Option<&str> {
+        match self {
+            Update::ResultsChanged { event, .. }
+            | Update::SetlistChanged { event }
+            | Update::VotingClosed { event }
+            | Update::NowPlaying { event, .. } => Some(event),
+            Update::SongHidden { .. }
+            | Update::SongUnhidden { .. }
+            | Update::SongDeleted { .. } => None,
+        }
+    }
+}
+
+/// Messages clients send to choose what they receive.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Adds topics, optionally only for the event with the given slug.
+    Subscribe {
+        topics: Vec<Topic>,
+        event: Option<String>,
+    },
+    Unsubscribe {
+        topics: Vec<Topic>,
+    },
+}
+
+/// The subscription of an `/events` stream, as in
+/// `/events?topics=results_changed&topics=now_playing&event=festorkestret`.
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionQuery {
+    #[serde(default)]
+    topics: Vec<Topic>,
+    event: Option<String>,
+}
+
+#[derive(Default)]
+struct Subscription {
+    topics: HashSet<Topic>,
+    event: Option<String>,
+}
+
+impl Subscription {
+    fn apply(&mut self, message: ClientMessage) {
+        match message {
+            ClientMessage::Subscribe { topics, event } => {
+                self.topics.extend(topics);
+                self.event = event;
+            }
+            ClientMessage::Unsubscribe { topics } => {
+                for topic in topics {
+                    self.topics.remove(&topic);
+                }
+            }
+        }
+    }
+
+    fn wants(&self, update: &Update) -> bool {
+        let event_matches = match (&self.event, update.event()) {
+            (Some(subscribed), Some(event)) => subscribed == event,
+            _ => true,
+        };
+
+        event_matches && self.topics.contains(&update.topic())
+    }
+}
+
+pub fn publish(tx: &Sender<Update>, update: Update) {
+    // sending only fails when nobody is connected
+    if tx.send(update).is_err() {
+        debug!("No clients to send update to");
+    }
+}
+
+/// Streams the same updates as `/websocket` as Server-Sent Events, for networks that
+/// break websockets.
+pub async fn sse_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SubscriptionQuery>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let subscription = Subscription {
+        topics: query.topics.into_iter().collect(),
+        event: query.event,
+    };
+
+    let updates = stream::unfold(
+        (state.tx.subscribe(), subscription),
+        |(mut rx, subscription)| async move {
+            let event = next_event(&mut rx, &subscription).await?;
+            Some((Ok(event), (rx, subscription)))
+        },
+    );
+
+    Sse::new(updates).keep_alive(KeepAlive::default())
+}
+
+/// Waits for the next update the subscription wants, or `None` when the server shuts down.
+async fn next_event(rx: &mut Receiver<Update>, subscription: &Subscription) -> Option<sse::Event> {
+    loop {
+        match rx.recv().await {
+            Ok(update) if subscription.wants(&update) => {
+                match sse::Event::default().json_data(&update) {
+                    Ok(event) => return Some(event),
+                    Err(err) => warn!("Failed to serialize update: {}", err),
+                }
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Client fell behind, skipped {} updates", skipped);
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+pub async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(|socket| handle_socket(socket, state))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
+    let mut rx = state.tx.subscribe();
+    let mut subscription = Subscription::default();
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(message) => subscription.apply(message),
+                    Err(err) => warn!("Invalid websocket message: {}", err),
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            update = rx.recv() => match update {
+                Ok(update) if subscription.wants(&update) => {
+                    let json = match serde_json::to_string(&update) {
+                        Ok(json) => json,
+                        Err(err) => {
+                            warn!("Failed to serialize update: {}", err);
+                            continue;
+                        }
+                    };
+
+                    if socket.send(Message::Text(json)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Client fell behind, skipped {} updates", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+
+    info!("Client connection ended");
+}
@@ -55,3 +55,12 @@ impl FromIterator<View> for View {
         View(result)
     }
 }
+
+/// Escapes text from users and requests, since `html!` puts its values into the page as they are.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
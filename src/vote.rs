@@ -1,46 +1,44 @@
 use crate::{
-    app::AppState, database::Song, errors::BadRequestError, html, icons,
-    page::page_with_view_before, view::View, vote_results::votes_updated,
+    app::AppState,
+    database::{Event, Song, Vote},
+    errors::{AppError, BadRequestError},
+    events::{event_by_slug, format_local_time},
+    html, icons,
+    join::{ensure_admitted, is_admitted},
+    page::page_with_view_before,
+    session::Session,
+    view::{escape, View},
+    vote_results::votes_updated,
 };
 use axum::{
     extract::{Path, State},
-    response::Redirect,
+    response::{IntoResponse, Redirect, Response},
 };
-use axum_extra::extract::CookieJar;
 use std::sync::Arc;
 use tracing::warn;
 
-const MAX_VOTES: i64 = 5;
+pub async fn current_vote_songs(State(state): State<Arc<AppState>>) -> Result<Redirect, AppError> {
+    Ok(match state.database.get_current_event().await? {
+        Some(event) => Redirect::to(&format!("/e/{}/vote", event.slug)),
+        None => Redirect::to("/"),
+    })
+}
 
 pub async fn vote_songs(
     State(state): State<Arc<AppState>>,
-    jar: CookieJar,
-) -> Result<View, Redirect> {
-    let session_id = match jar.get("session_id") {
-        Some(res) => res.value_trimmed(),
-        None => return Err(Redirect::to("/")),
-    };
+    Path(slug): Path<String>,
+    session: Session,
+) -> Result<Response, AppError> {
+    let session_id = &session.id;
+    let event = event_by_slug(&state.database, &slug).await?;
 
-    let votes: Vec<i32> = state
-        .database
-        .get_votes(session_id)
-        .await
-        .unwrap()
-        .iter()
-        .map(|x| x.song_id)
-        .collect();
+    if !is_admitted(&state.database, &event, session_id).await? {
+        return Ok(Redirect::to(&format!("/e/{}/join", event.slug)).into_response());
+    }
 
-    let songs = state
-        .database
-        .get_setlist()
-        .await
-        .unwrap()
-        .into_iter()
-        .filter(|x| !x.hidden)
-        .map(|x| song_card(votes.contains(&x.id), x))
-        .collect::<View>();
+    let votes = state.database.get_votes(event.id, session_id).await?;
 
-    let current_votes = state.database.count_votes(session_id).await.unwrap();
+    let current_votes = state.database.count_votes(event.id, session_id).await?;
 
     let sticky_info = html! {
         <div class="relative bg-inherit">
@@ -51,7 +49,7 @@ pub async fn vote_songs(
                         {"&nbsp;"}
                         <span>ud af</span>
                         {"&nbsp;"}
-                        <span>{MAX_VOTES}</span>
+                        <span>{event.vote_budget}</span>
                         {"&nbsp;"}
                         <span>stemmer</span>
                     </div>
@@ -63,86 +61,184 @@ pub async fn vote_songs(
                     </a>
                 </div>
             </div>
+            {voting_window_info(&event)}
+            {if event.voting_mode.is_ranked() {
+                html! {
+                    <p class="px-4 pt-2 text-sm text-center text-neutral-500">
+                        Stem i prioriteret rækkefølge, din første stemme tæller mest
+                    </p>
+                }
+            } else {
+                Default::default()
+            }}
             // spacing element which overlays song cards
             <div class="absolute w-full h-4 bg-inherit"></div>
         </div>
     };
 
-    let song_container = html! { <div class="flex flex-col gap-4 w-full max-w-lg">{songs}</div> };
+    let song_container = html! {
+        {song_list(&state, &event, &votes).await?}
+        <script
+            src="/assets/scripts/vote-updates.js?version=10"
+            data-event=&event.slug
+            data-topics="song_hidden song_unhidden song_deleted setlist_changed voting_closed"
+        ></script>
+    };
 
-    Ok(page_with_view_before(
-        song_container,
-        sticky_info,
-        "Setlist",
-    ))
+    Ok(page_with_view_before(song_container, sticky_info, &event.name).into_response())
 }
 
-pub async fn vote_for_song(
+/// The song cards alone, which the vote page fetches again when the setlist changes.
+pub async fn vote_song_list(
     State(state): State<Arc<AppState>>,
-    Path(song_id): Path<i32>,
-    jar: CookieJar,
-) -> Result<View, BadRequestError> {
-    let session_id = jar.get("session_id").unwrap().value_trimmed();
+    Path(slug): Path<String>,
+    session: Session,
+) -> Result<View, AppError> {
+    let event = event_by_slug(&state.database, &slug).await?;
+
+    ensure_admitted(&state.database, &event, &session.id).await?;
+
+    let votes = state.database.get_votes(event.id, &session.id).await?;
+
+    song_list(&state, &event, &votes).await
+}
+
+async fn song_list(state: &AppState, event: &Event, votes: &[Vote]) -> Result<View, AppError> {
+    let songs = state
+        .database
+        .get_event_setlist(event.id)
+        .await?
+        .into_iter()
+        .filter(|x| !x.hidden)
+        .map(|x| song_card(event, rank(votes, x.id), x, false))
+        .collect::<View>();
+
+    Ok(html! { <div id="songs" class="flex flex-col gap-4 w-full max-w-lg">{songs}</div> })
+}
 
-    warn!("New vote for song {} by {}", song_id, session_id);
+pub async fn vote_for_song(
+    State(state): State<Arc<AppState>>,
+    Path((slug, song_id)): Path<(String, i32)>,
+    session: Session,
+) -> Result<View, AppError> {
+    let session_id = session.existing()?;
+    let event = event_by_slug(&state.database, &slug).await?;
 
-    let vote_count = state.database.count_votes(session_id).await.unwrap();
+    warn!(
+        "New vote for song {} by {} at {}",
+        song_id, session_id, event.slug
+    );
 
-    if vote_count >= MAX_VOTES {
-        return Err(BadRequestError::TooManyVotes);
+    if !event.accepts_votes() {
+        return Err(BadRequestError::VotingClosed.into());
     }
 
-    let song = state
+    ensure_admitted(&state.database, &event, session_id).await?;
+
+    let (song, rank) = state
         .database
-        .create_vote(session_id, song_id)
-        .await
-        .unwrap();
+        .create_vote(event.id, session_id, song_id, event.vote_budget)
+        .await?;
 
-    votes_updated(&state.tx, &state.database).await;
+    votes_updated(&state.tx, &state.database, &event).await;
 
+    // ranks count the session's votes, so the new vote's rank is the number of votes
     Ok(html! {
-        {song_card(true, song)}
+        {song_card(&event, Some(rank), song, false)}
         <span id="current_votes" hx-swap-oob="true">
-            {vote_count + 1}
+            {rank}
         </span>
     })
 }
 
 pub async fn delete_vote(
     State(state): State<Arc<AppState>>,
-    Path(song_id): Path<i32>,
-    jar: CookieJar,
-) -> View {
-    let session_id = jar.get("session_id").unwrap().value_trimmed();
+    Path((slug, song_id)): Path<(String, i32)>,
+    session: Session,
+) -> Result<View, AppError> {
+    let session_id = session.existing()?;
+    let event = event_by_slug(&state.database, &slug).await?;
+
+    warn!(
+        "Delete vote for song {} by {} at {}",
+        song_id, session_id, event.slug
+    );
 
-    warn!("Delete vote for song {} by {}", song_id, session_id);
+    if !event.accepts_votes() {
+        return Err(BadRequestError::VotingClosed.into());
+    }
+
+    ensure_admitted(&state.database, &event, session_id).await?;
 
     let song = state
         .database
-        .delete_vote(session_id, song_id)
-        .await
-        .unwrap();
+        .delete_vote(event.id, session_id, song_id)
+        .await?;
 
-    votes_updated(&state.tx, &state.database).await;
+    votes_updated(&state.tx, &state.database, &event).await;
 
-    let vote_count = state.database.count_votes(session_id).await.unwrap();
+    let votes = state.database.get_votes(event.id, session_id).await?;
 
-    html! {
-        {song_card(false, song)}
+    // the remaining picks move up a rank
+    let reranked = match event.voting_mode.is_ranked() {
+        true => state
+            .database
+            .get_event_setlist(event.id)
+            .await?
+            .into_iter()
+            .filter_map(|song| rank(&votes, song.id).map(|rank| (rank, song)))
+            .map(|(rank, song)| song_card(&event, Some(rank), song, true))
+            .collect::<View>(),
+        false => Default::default(),
+    };
+
+    Ok(html! {
+        {song_card(&event, None, song, false)}
+        {reranked}
         <span id="current_votes" hx-swap-oob="true">
-            {vote_count}
+            {votes.len()}
         </span>
+    })
+}
+
+fn voting_window_info(event: &Event) -> View {
+    if !event.accepts_votes() {
+        return html! {
+            <p id="voting-window" class="px-4 pt-2 text-center text-red-500">
+                Afstemningen er lukket, der kan ikke længere stemmes
+            </p>
+        };
     }
+
+    // kept empty without a closing time, so the page has somewhere to say voting closed
+    match event.closes_at {
+        Some(closes_at) => html! {
+            <p id="voting-window" class="px-4 pt-2 text-sm text-center text-neutral-500">
+                {format!("Afstemningen lukker kl. {}", format_local_time(closes_at))}
+            </p>
+        },
+        None => html! { <p id="voting-window"></p> },
+    }
+}
+
+fn rank(votes: &[Vote], song_id: i32) -> Option<i32> {
+    votes
+        .iter()
+        .find(|vote| vote.song_id == song_id)
+        .map(|vote| vote.rank)
 }
 
-fn song_card(voted_for: bool, song: Song) -> View {
+fn song_card(event: &Event, rank: Option<i32>, song: Song, oob: bool) -> View {
+    let voted_for = rank.is_some();
+
     html! {
         <button
             {if voted_for {
-                format!("hx-delete=/vote/{}", song.id)
+                format!("hx-delete=/e/{}/vote/{}", event.slug, song.id)
             } else {
-                format!("hx-post=/vote/{}", song.id)
+                format!("hx-post=/e/{}/vote/{}", event.slug, song.id)
             }}
+            {if oob { "hx-swap-oob=true" } else { Default::default() }}
             hx-swap="outerHTML"
             id=format!("song-{}", song.id)
             class=format!(
@@ -151,17 +247,26 @@ fn song_card(voted_for: bool, song: Song) -> View {
             )
         >
             <div class="flex gap-2 justify-between items-start w-full">
-                <h2 class="text-2xl font-semibold text-left">{song.title}</h2>
-                <span class=if voted_for {
-                    "text-blue-500"
-                } else {
-                    "dark:text-neutral-700"
-                }>{icons::check_circle()}</span>
+                <h2 class="text-2xl font-semibold text-left">{escape(&song.title)}</h2>
+                {match rank {
+                    Some(rank) if event.voting_mode.is_ranked() => {
+                        html! { <span class="text-2xl font-bold text-blue-500">{rank}</span> }
+                    }
+                    _ => {
+                        html! {
+                            <span class=if voted_for {
+                                "text-blue-500"
+                            } else {
+                                "dark:text-neutral-700"
+                            }>{icons::check_circle()}</span>
+                        }
+                    }
+                }}
             </div>
 
-            <h4 class="text-sm text-neutral-500">{song.artist}</h4>
+            <h4 class="text-sm text-neutral-500">{escape(&song.artist)}</h4>
             {if let Some(description) = song.description {
-                html! { <p class="pt-2 text-left">{description}</p> }
+                html! { <p class="pt-2 text-left">{escape(&description)}</p> }
             } else {
                 Default::default()
             }}
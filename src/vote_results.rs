@@ -1,34 +1,122 @@
-use crate::{app::AppState, database::Database, html, page::page, view::View};
-use axum::extract::{ws::Message, State};
+use crate::{
+    app::AppState,
+    database::{Database, Event, VoteResult},
+    errors::AppError,
+    events::event_by_slug,
+    html,
+    page::page,
+    presenter::presenter_results,
+    updates::{publish, Update},
+    view::{escape, View},
+};
+use axum::{
+    extract::{Path, State},
+    response::Redirect,
+};
 use std::sync::Arc;
-use tokio::sync::watch::Sender;
+use tokio::sync::broadcast::Sender;
 use tracing::warn;
 
-pub async fn votes_updated(tx: &Sender<Message>, database: &Database) {
-    let vote_results = vote_results(database).await;
+/// The views showing the results of an event. Each fetches itself again when the votes change.
+pub struct VoteResultsFragments {
+    pub table: View,
+    pub presenter: View,
+}
+
+pub async fn votes_updated(tx: &Sender<Update>, database: &Database, event: &Event) {
+    let (results, voters) = match results_and_voters(database, event).await {
+        Ok(results) => results,
+        Err(err) => {
+            warn!("Failed to get vote results: {}", err);
+            return;
+        }
+    };
+
+    publish(
+        tx,
+        Update::ResultsChanged {
+            event: event.slug.clone(),
+            results,
+            voters,
+        },
+    );
+}
+
+/// Sends new results for every event with the song on its setlist, since hidden songs are left
+/// out of the results.
+pub async fn song_visibility_changed(tx: &Sender<Update>, database: &Database, song_id: i32) {
+    let events = match database.get_song_events(song_id).await {
+        Ok(events) => events,
+        Err(err) => {
+            warn!("Failed to get the events of song {}: {}", song_id, err);
+            return;
+        }
+    };
 
-    if tx.send(Message::Text(vote_results.to_string())).is_err() {
-        warn!("Failed to send message");
+    for event in events {
+        votes_updated(tx, database, &event).await;
     }
 }
 
-async fn vote_results(database: &Database) -> View {
-    let votes = database
-        .get_vote_results()
-        .await
-        .unwrap()
+/// The results of an event in the order the songs placed, and how many guests voted.
+pub(crate) async fn results_and_voters(
+    database: &Database,
+    event: &Event,
+) -> anyhow::Result<(Vec<VoteResult>, i64)> {
+    let results = database.get_vote_results(event).await?;
+    let voters = database.count_voters(event.id).await?;
+
+    Ok((results, voters))
+}
+
+pub async fn vote_results_fragments(
+    database: &Database,
+    event: &Event,
+) -> anyhow::Result<VoteResultsFragments> {
+    let (results, voters) = results_and_voters(database, event).await?;
+
+    Ok(VoteResultsFragments {
+        table: vote_results(event, &results),
+        presenter: presenter_results(event, &results, voters),
+    })
+}
+
+/// The results table alone, fetched by pages showing it when the votes change.
+pub async fn vote_results_table(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<View, AppError> {
+    let event = event_by_slug(&state.database, &slug).await?;
+
+    Ok(vote_results_fragments(&state.database, &event).await?.table)
+}
+
+/// The presenter bars alone, fetched by the presenter page when the votes change.
+pub async fn vote_results_presenter(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<View, AppError> {
+    let event = event_by_slug(&state.database, &slug).await?;
+
+    Ok(vote_results_fragments(&state.database, &event)
+        .await?
+        .presenter)
+}
+
+fn vote_results(event: &Event, results: &[VoteResult]) -> View {
+    let votes = results
         .iter()
         .map(|vote| {
             html! {
                 <tr class="odd:bg-gray-50 odd:dark:bg-neutral-950">
                     <td style="word-break: break-word" class="py-3 px-6">
-                        {&vote.song.title}
+                        {escape(&vote.song.title)}
                     </td>
                     <td style="word-break: break-word" class="py-3 px-6">
-                        {&vote.song.artist}
+                        {escape(&vote.song.artist)}
                     </td>
                     <td style="word-break: break-word" class="py-3 px-6">
-                        {&vote.vote_count}
+                        {&vote.points}
                     </td>
                 </tr>
             }
@@ -37,14 +125,28 @@ async fn vote_results(database: &Database) -> View {
 
     html! {
         <div
-            id="vote-results"
+            id=format!("vote-results-{}", event.id)
+            hx-get=format!("/e/{}/results/table", event.slug)
+            hx-trigger="results-changed from:body"
+            hx-swap="outerHTML"
             class="w-full max-w-lg rounded-lg border border-neutral-700 overflow-clip"
         >
+            {if event.accepts_votes() {
+                Default::default()
+            } else {
+                html! {
+                    <p class="py-3 px-6 font-bold text-center text-white bg-red-600">
+                        Afstemningen er lukket
+                    </p>
+                }
+            }}
             <table class="w-full text-left table-auto">
                 <tr class="font-bold border-b border-gray-700 bg-neutral-950">
                     <th class="py-3 px-6">Title</th>
                     <th class="py-3 px-6">Artist</th>
-                    <th class="py-3 px-6">Votes</th>
+                    <th class="py-3 px-6">
+                        {if event.voting_mode.is_ranked() { "Points" } else { "Votes" }}
+                    </th>
                 </tr>
                 {votes}
             </table>
@@ -52,14 +154,46 @@ async fn vote_results(database: &Database) -> View {
     }
 }
 
-pub async fn vote_result_page(State(state): State<Arc<AppState>>) -> View {
-    let vote_results = vote_results(&state.database).await;
+pub async fn current_vote_result_page(
+    State(state): State<Arc<AppState>>,
+) -> Result<Redirect, AppError> {
+    Ok(match state.database.get_current_event().await? {
+        Some(event) => Redirect::to(&format!("/e/{}/results", event.slug)),
+        None => Redirect::to("/"),
+    })
+}
+
+pub async fn vote_result_page(
+    State(state): State<Arc<AppState>>,
+    Path(slug): Path<String>,
+) -> Result<View, AppError> {
+    let event = event_by_slug(&state.database, &slug).await?;
+
+    let fragments = vote_results_fragments(&state.database, &event).await?;
 
-    page(
+    Ok(page(
         html! {
-            {vote_results}
-            <script src="/assets/scripts/vote-updates.js?version=3"></script>
+            <div class="flex flex-col gap-3 w-full max-w-lg">
+                <h1 class="text-lg">{escape(&event.name)}</h1>
+                {fragments.table}
+                <div class="flex flex-wrap gap-4">
+                    <a class="text-blue-500" href=format!("/e/{}/results/print", event.slug)>
+                        Udskriv setliste
+                    </a>
+                    <a class="text-blue-500" href=format!("/e/{}/results.csv", event.slug)>
+                        Hent som CSV
+                    </a>
+                    <a class="text-blue-500" href=format!("/e/{}/results.json", event.slug)>
+                        Hent som JSON
+                    </a>
+                </div>
+            </div>
+            <script
+                src="/assets/scripts/vote-updates.js?version=10"
+                data-event=&event.slug
+                data-topics="results_changed"
+            ></script>
         },
         "Vote results",
-    )
+    ))
 }
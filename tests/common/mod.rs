@@ -0,0 +1,204 @@
+// every test binary uses its own part of the harness
+#![allow(dead_code)]
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use axum_extra::extract::cookie::Key;
+use chrono::NaiveDate;
+use setlist_requests::{
+    admin::{hash_password, login_rate_limiter},
+    app::{app_router, AppState},
+    database::{Database, Event, Song, VotingMode},
+    rate_limit::{RateLimiter, RejectionCounts, VoteGuard},
+};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::broadcast::channel;
+use tower::ServiceExt;
+
+pub const ADMIN_USERNAME: &str = "admin";
+pub const ADMIN_PASSWORD: &str = "hunter2";
+
+pub fn date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2030, 6, 1).unwrap()
+}
+
+/// An event named after `slug` with `songs` songs on its setlist.
+pub async fn event_with_songs(
+    database: &Database,
+    slug: &str,
+    vote_budget: i32,
+    songs: usize,
+) -> (Event, Vec<Song>) {
+    let event = database
+        .create_event(slug, slug, date(), None, vote_budget)
+        .await
+        .unwrap();
+    let event = database
+        .update_event_settings(event.id, vote_budget, VotingMode::Approval, None, None)
+        .await
+        .unwrap();
+
+    let mut setlist = Vec::new();
+    for number in 0..songs {
+        let song = database
+            .add_song("Festorkestret", &format!("{} {}", slug, number), None)
+            .await
+            .unwrap();
+        setlist.push(song);
+    }
+
+    let song_ids: Vec<i32> = setlist.iter().map(|song| song.id).collect();
+    database
+        .set_event_setlist(event.id, &song_ids)
+        .await
+        .unwrap();
+
+    (event, setlist)
+}
+
+/// The app state the server builds from the environment, with generous rate limits.
+pub fn test_state(database: Database) -> Arc<AppState> {
+    rate_limited_state(database, 1000)
+}
+
+/// Like `test_state`, allowing only `votes_per_minute` votes per IP address and session.
+pub fn rate_limited_state(database: Database, votes_per_minute: u32) -> Arc<AppState> {
+    let (tx, _) = channel(64);
+
+    Arc::new(AppState {
+        tx,
+        database,
+        key: Key::generate(),
+        vote_guard: VoteGuard {
+            per_ip: RateLimiter::new(votes_per_minute, Duration::from_secs(60)),
+            per_session: RateLimiter::new(votes_per_minute, Duration::from_secs(60)),
+            rejected: RejectionCounts::default(),
+            trust_forwarded_for: false,
+        },
+        login_attempts: login_rate_limiter(),
+        public_url: None,
+        vote_budget: 5,
+    })
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Sends requests through the router like a browser, keeping the cookies it is given.
+pub struct TestClient {
+    router: Router,
+    /// The cookies sent with every request, by name.
+    pub cookies: HashMap<String, String>,
+    /// Extra headers sent with every request, like those a proxy adds.
+    pub headers: HeaderMap,
+}
+
+impl TestClient {
+    pub fn new(state: Arc<AppState>) -> TestClient {
+        TestClient {
+            router: app_router(state),
+            cookies: HashMap::new(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    pub async fn send(
+        &mut self,
+        method: Method,
+        uri: &str,
+        content_type: Option<&str>,
+        body: String,
+    ) -> TestResponse {
+        let cookies = self
+            .cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("HX-Request", "true")
+            .header(header::COOKIE, cookies);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+
+        let response = self
+            .router
+            .clone()
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+
+        for cookie in response.headers().get_all(header::SET_COOKIE) {
+            let cookie = cookie.to_str().unwrap();
+            let pair = cookie.split(';').next().unwrap();
+            if let Some((name, value)) = pair.split_once('=') {
+                self.cookies.insert(name.to_string(), value.to_string());
+            }
+        }
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        TestResponse {
+            status,
+            headers,
+            body: String::from_utf8(body.to_vec()).unwrap(),
+        }
+    }
+
+    pub async fn get(&mut self, uri: &str) -> TestResponse {
+        self.send(Method::GET, uri, None, String::new()).await
+    }
+
+    pub async fn post(&mut self, uri: &str) -> TestResponse {
+        self.send(Method::POST, uri, None, String::new()).await
+    }
+
+    pub async fn post_form(&mut self, uri: &str, form: &str) -> TestResponse {
+        self.send(
+            Method::POST,
+            uri,
+            Some("application/x-www-form-urlencoded"),
+            form.to_string(),
+        )
+        .await
+    }
+
+    pub async fn put(&mut self, uri: &str) -> TestResponse {
+        self.send(Method::PUT, uri, None, String::new()).await
+    }
+
+    pub async fn delete(&mut self, uri: &str) -> TestResponse {
+        self.send(Method::DELETE, uri, None, String::new()).await
+    }
+
+    /// Creates an admin and logs in as them.
+    pub async fn login(&mut self, database: &Database) {
+        database
+            .create_admin(ADMIN_USERNAME, &hash_password(ADMIN_PASSWORD).unwrap())
+            .await
+            .unwrap();
+
+        let response = self
+            .post_form(
+                "/login",
+                &format!("username={}&password={}", ADMIN_USERNAME, ADMIN_PASSWORD),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::SEE_OTHER);
+    }
+}
@@ -0,0 +1,341 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::{date, event_with_songs};
+use setlist_requests::{
+    database::{Database, RejectionReason, VotingMode},
+    errors::{AppError, VoteError},
+};
+use sqlx::PgPool;
+
+fn vote_error(err: anyhow::Error) -> VoteError {
+    match AppError::from(err) {
+        AppError::Vote(err) => err,
+        err => panic!("expected a vote error, got {:?}", err),
+    }
+}
+
+#[sqlx::test]
+async fn songs_can_be_added_edited_hidden_and_deleted(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+
+    let song = database
+        .add_song("Kim Larsen", "Papirsklip", Some("Fra 1979"))
+        .await
+        .unwrap();
+    assert_eq!(song.title, "Papirsklip");
+    assert_eq!(song.description.as_deref(), Some("Fra 1979"));
+    assert!(!song.hidden);
+
+    let song = database
+        .update_song(song.id, "Gasolin'", "Kvinde min", None)
+        .await
+        .unwrap();
+    assert_eq!(song.artist, "Gasolin'");
+    assert_eq!(song.description, None);
+
+    let fetched = database.get_song(song.id).await.unwrap().unwrap();
+    assert_eq!(fetched.title, "Kvinde min");
+
+    assert!(database.hide_song(song.id).await.unwrap().hidden);
+    assert!(!database.unhide_song(song.id).await.unwrap().hidden);
+
+    let setlist = database.get_setlist().await.unwrap();
+    assert!(setlist.iter().any(|song| song.title == "Kvinde min"));
+
+    database.delete_song(song.id).await.unwrap();
+    assert!(database.get_song(song.id).await.unwrap().is_none());
+}
+
+#[sqlx::test]
+async fn events_can_be_created_found_and_deleted(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+
+    let event = database
+        .create_event("sommerfest", "Sommerfest", date(), Some("Havnen"), 5)
+        .await
+        .unwrap();
+    assert!(event.open);
+    assert!(event.voting_open);
+    assert_eq!(event.venue.as_deref(), Some("Havnen"));
+
+    let by_id = database.get_event(event.id).await.unwrap().unwrap();
+    assert_eq!(by_id.slug, "sommerfest");
+    let by_slug = database
+        .get_event_by_slug("sommerfest")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_slug.id, event.id);
+
+    let events = database.get_events().await.unwrap();
+    assert_eq!(events[0].id, event.id);
+
+    assert_eq!(
+        database.get_current_event().await.unwrap().unwrap().id,
+        event.id
+    );
+    let closed = database.set_event_open(event.id, false).await.unwrap();
+    assert!(!closed.open);
+    assert_ne!(
+        database
+            .get_current_event()
+            .await
+            .unwrap()
+            .map(|event| event.id),
+        Some(event.id)
+    );
+
+    database.delete_event(event.id).await.unwrap();
+    assert!(database.get_event(event.id).await.unwrap().is_none());
+}
+
+#[sqlx::test]
+async fn event_settings_are_saved(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, songs) = event_with_songs(&database, "sommerfest", 5, 1).await;
+
+    let event = database
+        .update_event_settings(event.id, 3, VotingMode::Borda, Some(2), Some("SCENE"))
+        .await
+        .unwrap();
+    assert_eq!(event.vote_budget, 3);
+    assert_eq!(event.voting_mode, VotingMode::Borda);
+    assert_eq!(event.max_sessions_per_ip, Some(2));
+    assert!(event.join_code_matches(" scene "));
+    assert!(!event.join_code_matches("bar"));
+
+    let event = database
+        .set_now_playing(event.id, Some(songs[0].id))
+        .await
+        .unwrap();
+    assert_eq!(event.now_playing, Some(songs[0].id));
+}
+
+#[sqlx::test]
+async fn voting_closes_when_its_time_has_passed(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, _) = event_with_songs(&database, "sommerfest", 5, 0).await;
+
+    let event = database
+        .set_voting_window(event.id, true, Some(Utc::now() + Duration::hours(1)))
+        .await
+        .unwrap();
+    assert!(event.accepts_votes());
+    assert!(database.close_expired_voting().await.unwrap().is_empty());
+
+    database
+        .set_voting_window(event.id, true, Some(Utc::now() - Duration::minutes(1)))
+        .await
+        .unwrap();
+    let closed = database.close_expired_voting().await.unwrap();
+    assert_eq!(closed.len(), 1);
+    assert!(!closed[0].voting_open);
+    assert!(!closed[0].accepts_votes());
+}
+
+#[sqlx::test]
+async fn event_setlists_hold_the_chosen_songs(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, songs) = event_with_songs(&database, "sommerfest", 5, 3).await;
+
+    let setlist = database.get_event_setlist(event.id).await.unwrap();
+    assert_eq!(setlist.len(), 3);
+
+    database
+        .set_event_setlist(event.id, &[songs[1].id])
+        .await
+        .unwrap();
+    let setlist = database.get_event_setlist(event.id).await.unwrap();
+    assert_eq!(setlist.len(), 1);
+    assert_eq!(setlist[0].id, songs[1].id);
+
+    assert!(database
+        .set_event_setlist(event.id, &[songs[0].id, 4711])
+        .await
+        .is_err());
+    let setlist = database.get_event_setlist(event.id).await.unwrap();
+    assert_eq!(setlist.len(), 1);
+    assert_eq!(setlist[0].id, songs[1].id);
+}
+
+#[sqlx::test]
+async fn votes_are_ranked_counted_and_cleared(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, songs) = event_with_songs(&database, "sommerfest", 5, 3).await;
+    database.create_session("guest", None).await.unwrap();
+    database.create_session("other", None).await.unwrap();
+
+    for song in &songs {
+        database
+            .create_vote(event.id, "guest", song.id, event.vote_budget)
+            .await
+            .unwrap();
+    }
+    database
+        .create_vote(event.id, "other", songs[2].id, event.vote_budget)
+        .await
+        .unwrap();
+
+    assert_eq!(database.count_votes(event.id, "guest").await.unwrap(), 3);
+    assert_eq!(database.count_voters(event.id).await.unwrap(), 2);
+
+    // the votes after a deleted one move up a rank
+    database
+        .delete_vote(event.id, "guest", songs[0].id)
+        .await
+        .unwrap();
+    let mut votes = database.get_votes(event.id, "guest").await.unwrap();
+    votes.sort_by_key(|vote| vote.rank);
+    let ranked: Vec<_> = votes.iter().map(|vote| (vote.song_id, vote.rank)).collect();
+    assert_eq!(ranked, vec![(songs[1].id, 1), (songs[2].id, 2)]);
+
+    let results = database.get_vote_results(&event).await.unwrap();
+    assert_eq!(results[0].song.id, songs[2].id);
+    assert_eq!(results[0].vote_count, 2);
+    assert_eq!(results[0].points, 2);
+
+    database.clear_votes(event.id).await.unwrap();
+    assert_eq!(database.count_voters(event.id).await.unwrap(), 0);
+}
+
+#[sqlx::test]
+async fn results_leave_out_hidden_songs(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, songs) = event_with_songs(&database, "sommerfest", 5, 2).await;
+
+    database.hide_song(songs[0].id).await.unwrap();
+
+    let results = database.get_vote_results(&event).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].song.id, songs[1].id);
+}
+
+#[sqlx::test]
+async fn invalid_votes_are_rejected_with_their_reason(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, songs) = event_with_songs(&database, "sommerfest", 5, 2).await;
+    let other_song = database.add_song("Shu-bi-dua", "Rap", None).await.unwrap();
+    database.create_session("guest", None).await.unwrap();
+
+    let vote = |song_id| database.create_vote(event.id, "guest", song_id, event.vote_budget);
+
+    vote(songs[0].id).await.unwrap();
+    assert!(matches!(
+        vote_error(vote(songs[0].id).await.unwrap_err()),
+        VoteError::AlreadyVoted
+    ));
+    assert!(matches!(
+        vote_error(vote(-1).await.unwrap_err()),
+        VoteError::UnknownSong
+    ));
+    assert!(matches!(
+        vote_error(vote(other_song.id).await.unwrap_err()),
+        VoteError::NotOnSetlist
+    ));
+
+    database.hide_song(songs[1].id).await.unwrap();
+    assert!(matches!(
+        vote_error(vote(songs[1].id).await.unwrap_err()),
+        VoteError::HiddenSong
+    ));
+
+    let not_voted = database
+        .delete_vote(event.id, "guest", songs[1].id)
+        .await
+        .unwrap_err();
+    assert!(matches!(vote_error(not_voted), VoteError::NotVoted));
+}
+
+#[sqlx::test]
+async fn sessions_are_admitted_and_counted_per_ip(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, songs) = event_with_songs(&database, "sommerfest", 5, 1).await;
+
+    let session = database
+        .create_session("guest", Some("10.0.0.1"))
+        .await
+        .unwrap();
+    assert_eq!(session.ip.as_deref(), Some("10.0.0.1"));
+    assert!(database.get_session("guest").await.unwrap().is_some());
+    assert!(database.get_session("unknown").await.unwrap().is_none());
+
+    assert!(!database.is_admitted(event.id, "guest").await.unwrap());
+    database.admit_session(event.id, "guest").await.unwrap();
+    database.admit_session(event.id, "guest").await.unwrap();
+    assert!(database.is_admitted(event.id, "guest").await.unwrap());
+
+    database
+        .create_session("neighbour", Some("10.0.0.1"))
+        .await
+        .unwrap();
+    database
+        .create_vote(event.id, "guest", songs[0].id, event.vote_budget)
+        .await
+        .unwrap();
+    assert_eq!(
+        database
+            .count_ip_sessions(event.id, "10.0.0.1", "neighbour")
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        database
+            .count_ip_sessions(event.id, "10.0.0.1", "guest")
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[sqlx::test]
+async fn rejected_votes_are_counted_per_reason(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, _) = event_with_songs(&database, "sommerfest", 5, 0).await;
+
+    for reason in [
+        RejectionReason::SessionRateLimit,
+        RejectionReason::SessionRateLimit,
+        RejectionReason::SessionsPerIp,
+    ] {
+        database
+            .record_rejected_vote(event.id, "guest", Some("10.0.0.1"), reason, 1)
+            .await
+            .unwrap();
+    }
+
+    let mut rejected: Vec<_> = database
+        .get_rejected_votes(event.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|rejected| (rejected.reason.as_str(), rejected.count))
+        .collect();
+    rejected.sort();
+    assert_eq!(
+        rejected,
+        vec![("session_rate_limit", 2), ("sessions_per_ip", 1)]
+    );
+}
+
+#[sqlx::test]
+async fn admins_can_be_found_by_id_and_username(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+
+    let admin = database.create_admin("admin", "hash").await.unwrap();
+
+    let by_id = database.get_admin(admin.id).await.unwrap().unwrap();
+    assert_eq!(by_id.username, "admin");
+    let by_username = database
+        .get_admin_by_username("admin")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_username.id, admin.id);
+    assert!(database
+        .get_admin_by_username("nobody")
+        .await
+        .unwrap()
+        .is_none());
+}
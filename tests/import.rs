@@ -0,0 +1,102 @@
+use setlist_requests::{
+    database::{Database, MemoryStore, NewSong, Song},
+    errors::AppError,
+    import::{find_duplicates, parse_songs, Duplicate, ImportFormat},
+};
+
+fn song(artist: &str, title: &str) -> NewSong {
+    NewSong {
+        artist: artist.to_string(),
+        title: title.to_string(),
+        description: None,
+    }
+}
+
+#[test]
+fn every_format_reads_the_same_songs() {
+    let expected = vec![
+        song("Kim Larsen", "Papirsklip"),
+        song("Gasolin'", "Kvinde min"),
+    ];
+
+    let files = [
+        (
+            "repertoire.csv",
+            "\u{feff}Titel,Kunstner,Beskrivelse\nPapirsklip,Kim Larsen,\n\"Kvinde min\",Gasolin',\n",
+        ),
+        ("no-header.csv", "Papirsklip,Kim Larsen\nKvinde min,Gasolin'\n"),
+        (
+            "spotify.csv",
+            "Track URI,Track Name,Artist Name(s)\nspotify:track:1,Papirsklip,Kim Larsen\nspotify:track:2,Kvinde min,Gasolin'\n",
+        ),
+        (
+            "repertoire.json",
+            r#"[{"title": "Papirsklip", "artist": "Kim Larsen"}, {"name": "Kvinde min", "artist": "Gasolin'"}]"#,
+        ),
+        (
+            "playlist.m3u",
+            "#EXTM3U\n#EXTINF:213,Kim Larsen - Papirsklip\nmusik/01.mp3\n\nmusik/Gasolin' - Kvinde min.flac\n",
+        ),
+        ("repertoire.txt", "Kim Larsen - Papirsklip\n\nGasolin' - Kvinde min\n"),
+    ];
+
+    for (name, contents) in files {
+        let songs = parse_songs(ImportFormat::from_file_name(name), contents).unwrap();
+        assert_eq!(songs, expected, "{}", name);
+    }
+}
+
+#[test]
+fn songs_without_an_artist_point_at_their_row() {
+    let missing = parse_songs(ImportFormat::Csv, "Papirsklip,Kim Larsen\nKvinde min,\n");
+    assert!(
+        matches!(missing, Err(AppError::Validation(message)) if message.starts_with("Række 2"))
+    );
+
+    let missing = parse_songs(ImportFormat::Text, "Kim Larsen - Papirsklip\nKvinde min\n");
+    assert!(
+        matches!(missing, Err(AppError::Validation(message)) if message.starts_with("Linje 2"))
+    );
+}
+
+#[test]
+fn duplicates_are_found_in_the_repertoire_and_the_file() {
+    let repertoire = vec![Song {
+        id: 1,
+        artist: "Kim Larsen".to_string(),
+        title: "Papirsklip".to_string(),
+        description: None,
+        hidden: false,
+    }];
+    let songs = vec![
+        song("kim larsen", "PAPIRSKLIP"),
+        song("Gasolin'", "Kvinde min"),
+        song("Gasolin'", "kvinde min"),
+    ];
+
+    let duplicates: Vec<_> = find_duplicates(songs, &repertoire)
+        .into_iter()
+        .map(|row| row.duplicate)
+        .collect();
+    assert_eq!(
+        duplicates,
+        vec![Some(Duplicate::Existing), None, Some(Duplicate::InFile)]
+    );
+}
+
+#[tokio::test]
+async fn imported_songs_join_the_repertoire() {
+    let database = Database::from_store(MemoryStore::default());
+    database
+        .add_song("Kim Larsen", "Papirsklip", None)
+        .await
+        .unwrap();
+
+    let added = database
+        .add_songs(&[song("Gasolin'", "Kvinde min"), song("Shu-bi-dua", "Rap")])
+        .await
+        .unwrap();
+
+    assert_eq!(added.len(), 2);
+    assert_eq!(database.get_setlist().await.unwrap().len(), 3);
+}
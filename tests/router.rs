@@ -0,0 +1,817 @@
+mod common;
+
+use axum::http::{header, Method, StatusCode};
+use common::{
+    event_with_songs, rate_limited_state, test_state, TestClient, ADMIN_PASSWORD, ADMIN_USERNAME,
+};
+use setlist_requests::{
+    admin::hash_password,
+    database::{Database, RejectionReason, VotingMode},
+    rate_limit::save_rejected_votes,
+    updates::Update,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+#[sqlx::test]
+async fn guests_can_vote_and_take_back_their_vote(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 2).await;
+    let mut guest = TestClient::new(state.clone());
+
+    let page = guest.get("/e/koncert/vote").await;
+    assert_eq!(page.status, StatusCode::OK);
+    assert!(page.body.contains(&format!("id=\"song-{}\"", songs[0].id)));
+
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::OK);
+    assert!(voted
+        .body
+        .contains(&format!("hx-delete=/e/koncert/vote/{}", songs[0].id)));
+
+    let results = state.database.get_vote_results(&event).await.unwrap();
+    assert_eq!(results[0].song.id, songs[0].id);
+    assert_eq!(results[0].vote_count, 1);
+
+    let removed = guest
+        .delete(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(removed.status, StatusCode::OK);
+    assert!(removed
+        .body
+        .contains(&format!("hx-post=/e/koncert/vote/{}", songs[0].id)));
+
+    let votes = guest.get("/api/v1/events/koncert/votes").await;
+    assert_eq!(votes.status, StatusCode::OK);
+    assert!(votes.body.contains("\"votes\":[]"));
+}
+
+#[sqlx::test]
+async fn votes_need_a_session_from_an_earlier_visit(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (_, songs) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    let mut guest = TestClient::new(state);
+
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::UNAUTHORIZED);
+
+    let voted = guest
+        .post(&format!("/api/v1/events/koncert/votes/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::UNAUTHORIZED);
+    assert!(voted.body.starts_with("{\"error\""));
+
+    // the vote page issues the session
+    guest.get("/e/koncert/vote").await;
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn api_clients_start_a_session_and_vote(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    state
+        .database
+        .update_event_settings(event.id, 5, VotingMode::Approval, None, Some("SCENE"))
+        .await
+        .unwrap();
+    let mut client = TestClient::new(state.clone());
+
+    let started = client.post("/api/v1/session").await;
+    assert_eq!(started.status, StatusCode::CREATED);
+    assert!(started.headers.contains_key(header::SET_COOKIE));
+    assert_eq!(
+        client.post("/api/v1/session").await.status,
+        StatusCode::NO_CONTENT
+    );
+
+    let joined = client
+        .send(
+            Method::POST,
+            "/api/v1/events/koncert/join",
+            Some("application/json"),
+            r#"{"code": "scene"}"#.to_string(),
+        )
+        .await;
+    assert_eq!(joined.status, StatusCode::NO_CONTENT);
+
+    let voted = client
+        .post(&format!("/api/v1/events/koncert/votes/{}", songs[0].id))
+        .await;
+    assert!(voted.status.is_success(), "{}", voted.status);
+    assert_eq!(state.database.count_voters(event.id).await.unwrap(), 1);
+}
+
+#[sqlx::test]
+async fn only_the_guest_pages_issue_sessions(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    event_with_songs(&state.database, "koncert", 5, 1).await;
+    let mut guest = TestClient::new(state);
+
+    for uri in [
+        "/api/smoke",
+        "/api/v1/events",
+        "/login",
+        "/e/koncert/results",
+    ] {
+        let response = guest.get(uri).await;
+        assert!(
+            !response.headers.contains_key(header::SET_COOKIE),
+            "{} issued a session",
+            uri
+        );
+    }
+
+    let page = guest.get("/e/koncert/vote").await;
+    assert!(page.headers.contains_key(header::SET_COOKIE));
+}
+
+#[sqlx::test]
+async fn forged_session_cookies_are_replaced(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (_, songs) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    let mut guest = TestClient::new(state);
+    guest
+        .cookies
+        .insert("session_id".to_string(), "abcdef".to_string());
+
+    let voted = guest
+        .post(&format!("/api/v1/events/koncert/votes/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::UNAUTHORIZED);
+
+    guest.get("/e/koncert/vote").await;
+    assert_ne!(guest.cookies["session_id"], "abcdef");
+
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn votes_beyond_the_budget_are_forbidden(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (_, songs) = event_with_songs(&state.database, "koncert", 2, 3).await;
+    let mut guest = TestClient::new(state);
+    guest.get("/e/koncert/vote").await;
+
+    for song in &songs[..2] {
+        let voted = guest.post(&format!("/e/koncert/vote/{}", song.id)).await;
+        assert_eq!(voted.status, StatusCode::OK);
+    }
+
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[2].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::FORBIDDEN);
+    assert!(voted.body.contains("Du har brugt alle dine stemmer"));
+
+    let voted = guest
+        .post(&format!("/api/v1/events/koncert/votes/{}", songs[2].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::FORBIDDEN);
+    assert!(voted.body.starts_with("{\"error\":"));
+}
+
+#[sqlx::test]
+async fn admins_set_the_vote_budget_of_an_event(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 2).await;
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+    let mut guest = TestClient::new(state);
+    guest.get("/e/koncert/vote").await;
+
+    let settings = format!("/setlist/events/{}/settings", event.id);
+    let form = |budget: i32| {
+        format!(
+            "vote_budget={}&voting_mode=approval&max_sessions_per_ip=&join_code=",
+            budget
+        )
+    };
+
+    let refused = admin.post_form(&settings, &form(0)).await;
+    assert_eq!(refused.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(refused
+        .body
+        .contains("Antal stemmer skal være mellem 1 og 100"));
+
+    let saved = admin.post_form(&settings, &form(1)).await;
+    assert_eq!(saved.status, StatusCode::OK);
+
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::OK);
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[1].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn rejected_votes_are_saved_as_counts(pool: PgPool) {
+    let state = rate_limited_state(Database::new_from_pool(pool), 1);
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 3).await;
+    let mut guest = TestClient::new(state.clone());
+    guest.get("/e/koncert/vote").await;
+
+    for (song, status) in songs.iter().zip([
+        StatusCode::OK,
+        StatusCode::TOO_MANY_REQUESTS,
+        StatusCode::TOO_MANY_REQUESTS,
+    ]) {
+        let voted = guest.post(&format!("/e/koncert/vote/{}", song.id)).await;
+        assert_eq!(voted.status, status);
+    }
+
+    // nothing is written until the counts are saved
+    let rejected = state.database.get_rejected_votes(event.id).await.unwrap();
+    assert!(rejected.is_empty());
+
+    save_rejected_votes(&state).await.unwrap();
+    let rejected = state.database.get_rejected_votes(event.id).await.unwrap();
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].reason, RejectionReason::SessionRateLimit);
+    assert_eq!(rejected[0].count, 2);
+}
+
+#[sqlx::test]
+async fn guests_need_the_join_code_to_vote(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    state
+        .database
+        .update_event_settings(event.id, 5, VotingMode::Approval, None, Some("SCENE"))
+        .await
+        .unwrap();
+    let mut guest = TestClient::new(state);
+    guest.get("/e/koncert/vote").await;
+
+    let vote = format!("/e/koncert/vote/{}", songs[0].id);
+    assert_eq!(guest.post(&vote).await.status, StatusCode::FORBIDDEN);
+
+    let wrong = guest.post_form("/e/koncert/join", "code=SCENER").await;
+    assert_eq!(wrong.status, StatusCode::OK);
+    assert_eq!(guest.post(&vote).await.status, StatusCode::FORBIDDEN);
+
+    // QR links carry the code, which is typed in without regard to case
+    let joined = guest.get("/e/koncert/join?code=scene").await;
+    assert_eq!(joined.status, StatusCode::SEE_OTHER);
+    assert_eq!(guest.post(&vote).await.status, StatusCode::OK);
+}
+
+/// The QR page of the event as seen through a proxy, logged in as the admin.
+async fn qr_page_behind_proxy(database: &Database, event_id: i32, trust_proxy: bool) -> String {
+    let mut state = test_state(database.clone());
+    Arc::get_mut(&mut state)
+        .unwrap()
+        .vote_guard
+        .trust_forwarded_for = trust_proxy;
+    let mut admin = TestClient::new(state);
+    admin
+        .post_form(
+            "/login",
+            &format!("username={}&password={}", ADMIN_USERNAME, ADMIN_PASSWORD),
+        )
+        .await;
+
+    for (name, value) in [
+        ("Host", "scene.example"),
+        ("X-Forwarded-Proto", "https"),
+        ("X-Forwarded-Host", "andet.example"),
+    ] {
+        admin.headers.insert(name, value.parse().unwrap());
+    }
+
+    admin
+        .get(&format!("/setlist/events/{}/qr", event_id))
+        .await
+        .body
+}
+
+#[sqlx::test]
+async fn qr_codes_only_follow_a_trusted_proxy(pool: PgPool) {
+    let database = Database::new_from_pool(pool);
+    let (event, _) = event_with_songs(&database, "koncert", 5, 1).await;
+    database
+        .update_event_settings(event.id, 5, VotingMode::Approval, None, Some("SCENE"))
+        .await
+        .unwrap();
+    database
+        .create_admin(ADMIN_USERNAME, &hash_password(ADMIN_PASSWORD).unwrap())
+        .await
+        .unwrap();
+
+    let untrusted = qr_page_behind_proxy(&database, event.id, false).await;
+    assert!(untrusted.contains("http://scene.example/e/koncert/join?code=SCENE"));
+
+    let trusted = qr_page_behind_proxy(&database, event.id, true).await;
+    assert!(trusted.contains("https://andet.example/e/koncert/join?code=SCENE"));
+}
+
+#[sqlx::test]
+async fn votes_at_the_cap_say_why_they_were_rejected(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (_, songs) = event_with_songs(&state.database, "koncert", 2, 4).await;
+    state.database.hide_song(songs[3].id).await.unwrap();
+    let mut guest = TestClient::new(state);
+    guest.get("/e/koncert/vote").await;
+
+    for song in &songs[..2] {
+        let voted = guest.post(&format!("/e/koncert/vote/{}", song.id)).await;
+        assert_eq!(voted.status, StatusCode::OK);
+    }
+
+    let statuses = [
+        guest
+            .post(&format!("/e/koncert/vote/{}", songs[0].id))
+            .await
+            .status,
+        guest
+            .post(&format!("/e/koncert/vote/{}", songs[3].id))
+            .await
+            .status,
+        guest
+            .post(&format!("/e/koncert/vote/{}", songs[2].id))
+            .await
+            .status,
+    ];
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::CONFLICT,
+            StatusCode::NOT_FOUND,
+            StatusCode::FORBIDDEN
+        ]
+    );
+}
+
+#[sqlx::test]
+async fn logins_are_rate_limited_per_ip(pool: PgPool) {
+    let mut state = test_state(Database::new_from_pool(pool));
+    Arc::get_mut(&mut state)
+        .unwrap()
+        .vote_guard
+        .trust_forwarded_for = true;
+    state
+        .database
+        .create_admin(ADMIN_USERNAME, &hash_password(ADMIN_PASSWORD).unwrap())
+        .await
+        .unwrap();
+    let login = format!("username={}&password={}", ADMIN_USERNAME, ADMIN_PASSWORD);
+
+    let mut guesser = TestClient::new(state.clone());
+    guesser
+        .headers
+        .insert("X-Forwarded-For", "10.0.0.1".parse().unwrap());
+    for _ in 0..10 {
+        let wrong = guesser
+            .post_form("/login", "username=admin&password=forkert")
+            .await;
+        assert_eq!(wrong.status, StatusCode::OK);
+    }
+    let limited = guesser.post_form("/login", &login).await;
+    assert_eq!(limited.status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(limited.body.contains("For mange forsøg"));
+
+    let mut admin = TestClient::new(state);
+    admin
+        .headers
+        .insert("X-Forwarded-For", "10.0.0.2".parse().unwrap());
+    let logged_in = admin.post_form("/login", &login).await;
+    assert_eq!(logged_in.status, StatusCode::SEE_OTHER);
+}
+
+#[sqlx::test]
+async fn join_codes_cannot_be_guessed_quickly(pool: PgPool) {
+    let state = rate_limited_state(Database::new_from_pool(pool), 2);
+    let (event, _) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    state
+        .database
+        .update_event_settings(event.id, 5, VotingMode::Approval, None, Some("SCENE"))
+        .await
+        .unwrap();
+    let mut guest = TestClient::new(state.clone());
+    guest.get("/e/koncert/join").await;
+
+    let statuses = [
+        guest.post_form("/e/koncert/join", "code=AAAA").await.status,
+        guest.get("/e/koncert/join?code=AAAB").await.status,
+        guest
+            .post_form("/e/koncert/join", "code=SCENE")
+            .await
+            .status,
+    ];
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::OK,
+            StatusCode::OK,
+            StatusCode::TOO_MANY_REQUESTS
+        ]
+    );
+
+    save_rejected_votes(&state).await.unwrap();
+    let rejected = state.database.get_rejected_votes(event.id).await.unwrap();
+    assert_eq!(rejected[0].count, 1);
+}
+
+#[sqlx::test]
+async fn join_codes_are_long_plain_letters_and_digits(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, _) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+
+    let settings = format!("/setlist/events/{}/settings", event.id);
+    let form = |code: &str| {
+        format!(
+            "vote_budget=5&voting_mode=approval&max_sessions_per_ip=&join_code={}",
+            code
+        )
+    };
+
+    for code in ["ABC", "SCENE%C3%86", "SCE%20NE"] {
+        let refused = admin.post_form(&settings, &form(code)).await;
+        assert_eq!(refused.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", code);
+    }
+    let saved = admin.post_form(&settings, &form("SCENE1")).await;
+    assert_eq!(saved.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn hidden_songs_leave_the_guest_page_and_cannot_be_voted_for(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (_, songs) = event_with_songs(&state.database, "koncert", 5, 2).await;
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+    let mut guest = TestClient::new(state.clone());
+    guest.get("/e/koncert/vote").await;
+
+    let song_card = format!("id=\"song-{}\"", songs[0].id);
+
+    let hidden = admin.put(&format!("/setlist/{}/hide", songs[0].id)).await;
+    assert_eq!(hidden.status, StatusCode::OK);
+    assert!(!guest
+        .get("/e/koncert/songs")
+        .await
+        .body
+        .contains(&song_card));
+
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::NOT_FOUND);
+
+    let shown = admin.put(&format!("/setlist/{}/unhide", songs[0].id)).await;
+    assert_eq!(shown.status, StatusCode::OK);
+    assert!(guest
+        .get("/e/koncert/songs")
+        .await
+        .body
+        .contains(&song_card));
+
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::OK);
+}
+
+#[sqlx::test]
+async fn hiding_a_song_sends_new_results(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (_, songs) = event_with_songs(&state.database, "koncert", 5, 2).await;
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+    let mut updates = state.tx.subscribe();
+
+    admin.put(&format!("/setlist/{}/hide", songs[0].id)).await;
+    admin
+        .put(&format!("/api/v1/songs/{}/unhide", songs[0].id))
+        .await;
+
+    let mut results_sent = 0;
+    while let Ok(update) = updates.try_recv() {
+        if let Update::ResultsChanged { event, .. } = update {
+            assert_eq!(event, "koncert");
+            results_sent += 1;
+        }
+    }
+    assert_eq!(results_sent, 2);
+}
+
+#[sqlx::test]
+async fn closing_an_event_sends_new_results(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, _) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+    let mut updates = state.tx.subscribe();
+
+    admin
+        .put(&format!("/setlist/events/{}/close", event.id))
+        .await;
+
+    let mut kinds = Vec::new();
+    while let Ok(update) = updates.try_recv() {
+        match update {
+            Update::VotingClosed { .. } => kinds.push("voting_closed"),
+            Update::ResultsChanged { .. } => kinds.push("results_changed"),
+            _ => {}
+        }
+    }
+    assert_eq!(kinds, vec!["voting_closed", "results_changed"]);
+}
+
+#[sqlx::test]
+async fn admins_can_clear_the_votes_of_an_event(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+    let mut guest = TestClient::new(state.clone());
+    guest.get("/e/koncert/vote").await;
+
+    guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(state.database.count_voters(event.id).await.unwrap(), 1);
+
+    let cleared = admin
+        .post(&format!("/setlist/events/{}/votes/clear", event.id))
+        .await;
+    assert_eq!(cleared.status, StatusCode::OK);
+    assert_eq!(state.database.count_voters(event.id).await.unwrap(), 0);
+
+    guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    let cleared = admin.delete("/api/v1/events/koncert/votes").await;
+    assert_eq!(cleared.status, StatusCode::NO_CONTENT);
+    assert_eq!(state.database.count_voters(event.id).await.unwrap(), 0);
+}
+
+#[sqlx::test]
+async fn admin_routes_need_a_login(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    let mut guest = TestClient::new(state);
+
+    assert_eq!(guest.get("/setlist").await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        guest
+            .post(&format!("/setlist/events/{}/votes/clear", event.id))
+            .await
+            .status,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        guest
+            .put(&format!("/api/v1/songs/{}/hide", songs[0].id))
+            .await
+            .status,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[sqlx::test]
+async fn wrong_passwords_do_not_log_in(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+    let mut guest = TestClient::new(state);
+
+    let failed = guest
+        .post_form("/login", "username=admin&password=hunter3")
+        .await;
+    assert_eq!(failed.status, StatusCode::OK);
+    assert!(failed.body.contains("Forkert brugernavn eller adgangskode"));
+    assert!(!failed.headers.contains_key(header::SET_COOKIE));
+    assert_eq!(guest.get("/setlist").await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn logging_out_revokes_the_admin_cookie(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+
+    let response = admin.get("/setlist").await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let cookie = admin.cookies["admin_session"].clone();
+    assert_eq!(admin.post("/logout").await.status, StatusCode::SEE_OTHER);
+
+    // a copy of the cookie from before the logout no longer works
+    admin.cookies.insert("admin_session".to_string(), cookie);
+    assert_eq!(admin.get("/setlist").await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn the_api_reports_song_errors_like_the_pages(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+
+    let missing = admin.get("/api/v1/songs/4711").await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+    assert_eq!(missing.body, r#"{"error":"Sangen findes ikke"}"#);
+
+    let untitled = admin
+        .send(
+            Method::POST,
+            "/api/v1/songs",
+            Some("application/json"),
+            r#"{"artist": "Kim Larsen", "title": " "}"#.to_string(),
+        )
+        .await;
+    assert_eq!(untitled.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        untitled.body,
+        r#"{"error":"Sangen skal have en titel og en kunstner"}"#
+    );
+}
+
+#[sqlx::test]
+async fn results_export_in_the_order_the_songs_placed(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (_, songs) = event_with_songs(&state.database, "koncert", 5, 3).await;
+    let mut guest = TestClient::new(state);
+    guest.get("/e/koncert/vote").await;
+    guest
+        .post(&format!("/e/koncert/vote/{}", songs[1].id))
+        .await;
+
+    let csv = guest.get("/e/koncert/results.csv").await;
+    assert_eq!(csv.status, StatusCode::OK);
+    assert_eq!(csv.headers[header::CONTENT_TYPE], "text/csv; charset=utf-8");
+    let rows: Vec<&str> = csv.body.trim_start_matches('\u{feff}').lines().collect();
+    assert_eq!(rows[0], "Placering,Titel,Kunstner,Stemmer,Point");
+    assert_eq!(rows[1], format!("1,{},Festorkestret,1,1", songs[1].title));
+    assert_eq!(rows.len(), 4);
+
+    let json = guest.get("/vote/results.json").await;
+    assert_eq!(json.status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&json.body).unwrap();
+    assert_eq!(json["voters"], 1);
+    assert_eq!(json["results"][0]["title"], songs[1].title);
+    assert_eq!(json["results"][0]["position"], 1);
+
+    let print = guest.get("/e/koncert/results/print?top=2").await;
+    assert_eq!(print.status, StatusCode::OK);
+    assert_eq!(print.body.matches("<li>").count(), 2);
+    assert!(print.body.contains(&songs[1].title));
+}
+
+#[sqlx::test]
+async fn csv_exports_do_not_run_song_titles_as_formulas(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, mut songs) = event_with_songs(&state.database, "koncert", 5, 0).await;
+    for (artist, title) in [("@kunstner", "=HYPERLINK(\"x\")"), ("Kim Larsen", "-ish")] {
+        songs.push(state.database.add_song(artist, title, None).await.unwrap());
+    }
+    let song_ids: Vec<i32> = songs.iter().map(|song| song.id).collect();
+    state
+        .database
+        .set_event_setlist(event.id, &song_ids)
+        .await
+        .unwrap();
+    let mut guest = TestClient::new(state);
+
+    let csv = guest.get("/e/koncert/results.csv").await;
+    let body = csv.body.trim_start_matches('\u{feff}');
+    assert!(body.contains(r#"'=HYPERLINK(""x"")"#), "{}", body);
+    assert!(body.contains("'@kunstner"), "{}", body);
+    assert!(body.contains("'-ish"), "{}", body);
+}
+
+#[sqlx::test]
+async fn results_views_fetch_the_current_votes(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 3).await;
+    let mut guest = TestClient::new(state);
+    guest.get("/e/koncert/vote").await;
+    guest
+        .post(&format!("/e/koncert/vote/{}", songs[1].id))
+        .await;
+
+    let table = guest.get("/e/koncert/results/table").await;
+    assert_eq!(table.status, StatusCode::OK);
+    assert!(table
+        .body
+        .starts_with(&format!(r#"<div id="vote-results-{}""#, event.id)));
+    assert!(table.body.contains(&songs[1].title));
+
+    let presenter = guest.get("/e/koncert/results/presenter").await;
+    assert_eq!(presenter.status, StatusCode::OK);
+    assert!(presenter
+        .body
+        .starts_with(&format!(r#"<div id="presenter-{}""#, event.id)));
+    assert!(presenter.body.contains(&songs[1].title));
+
+    let missing = guest.get("/e/findes-ikke/results/table").await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn event_names_are_shown_as_text(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let event = state
+        .database
+        .create_event(
+            "koncert",
+            "<script>alert(1)</script>",
+            common::date(),
+            Some("<b>Vega</b>"),
+            5,
+        )
+        .await
+        .unwrap();
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+
+    for uri in [
+        "/".to_string(),
+        "/e/koncert/results".to_string(),
+        "/e/koncert/results/print".to_string(),
+        "/setlist/events".to_string(),
+        format!("/setlist/events/{}", event.id),
+        format!("/setlist/events/{}/qr", event.id),
+        format!("/setlist/events/{}/present", event.id),
+    ] {
+        let page = admin.get(&uri).await;
+        assert_eq!(page.status, StatusCode::OK, "{}", uri);
+        assert!(!page.body.contains("<script>alert(1)"), "{}", uri);
+        assert!(!page.body.contains("<b>Vega"), "{}", uri);
+    }
+}
+
+#[sqlx::test]
+async fn error_pages_do_not_repeat_markup_from_the_request(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let mut guest = TestClient::new(state);
+
+    let missing = guest
+        .get("/e/%3Cscript%3Ealert(1)%3C%2Fscript%3E/vote")
+        .await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+    assert!(missing.body.contains("Arrangementet findes ikke"));
+    assert!(!missing.body.contains("<script>alert(1)"));
+}
+
+#[sqlx::test]
+async fn imported_songs_are_shown_as_text(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let mut admin = TestClient::new(state.clone());
+    admin.login(&state.database).await;
+
+    let body = "--boundary\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"sange.txt\"\r\n\r\n\
+        Kim Larsen - <img src=x onerror=alert(1)>\r\n\
+        --boundary--\r\n";
+    let preview = admin
+        .send(
+            Method::POST,
+            "/setlist/import/preview",
+            Some("multipart/form-data; boundary=boundary"),
+            body.to_string(),
+        )
+        .await;
+    assert_eq!(preview.status, StatusCode::OK);
+    assert!(preview.body.contains("&lt;img src=x onerror=alert(1)&gt;"));
+    assert!(!preview.body.contains("<img"));
+}
+
+#[sqlx::test]
+async fn closed_events_refuse_votes(pool: PgPool) {
+    let state = test_state(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&state.database, "koncert", 5, 1).await;
+    let mut guest = TestClient::new(state.clone());
+    guest.get("/e/koncert/vote").await;
+
+    state
+        .database
+        .set_event_open(event.id, false)
+        .await
+        .unwrap();
+
+    let voted = guest
+        .post(&format!("/e/koncert/vote/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::FORBIDDEN);
+    assert!(voted.body.contains("Afstemningen er lukket"));
+
+    let voted = guest
+        .post(&format!("/api/v1/events/koncert/votes/{}", songs[0].id))
+        .await;
+    assert_eq!(voted.status, StatusCode::FORBIDDEN);
+}
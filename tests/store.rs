@@ -0,0 +1,203 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::event_with_songs;
+use setlist_requests::{
+    database::{Database, MemoryStore, NewSong, RejectionReason, SqliteStore, Store},
+    errors::{AppError, VoteError},
+};
+
+/// Runs the same operations against a store as the Postgres tests do, so every backend
+/// behaves alike.
+async fn behaves_like_postgres(database: Database) {
+    let (event, songs) = event_with_songs(&database, "koncert", 3, 3).await;
+    let other_song = database.add_song("Shu-bi-dua", "Rap", None).await.unwrap();
+    let imported = database
+        .add_songs(&[NewSong {
+            artist: "Gasolin'".to_string(),
+            title: "Kvinde min".to_string(),
+            description: Some("Fra 1974".to_string()),
+        }])
+        .await
+        .unwrap();
+    assert_eq!(imported[0].description.as_deref(), Some("Fra 1974"));
+    database
+        .create_session("guest", Some("10.0.0.1"))
+        .await
+        .unwrap();
+    database
+        .create_session("other", Some("10.0.0.1"))
+        .await
+        .unwrap();
+
+    assert_eq!(database.get_event_setlist(event.id).await.unwrap().len(), 3);
+
+    // an unknown song leaves the setlist as it was
+    assert!(database
+        .set_event_setlist(event.id, &[songs[0].id, 4711])
+        .await
+        .is_err());
+    assert_eq!(database.get_event_setlist(event.id).await.unwrap().len(), 3);
+
+    assert_eq!(
+        database.get_song_events(songs[0].id).await.unwrap()[0].id,
+        event.id
+    );
+    assert!(database
+        .get_song_events(other_song.id)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        database.get_current_event().await.unwrap().unwrap().id,
+        event.id
+    );
+
+    for song in &songs {
+        let (_, rank) = database
+            .create_vote(event.id, "guest", song.id, event.vote_budget)
+            .await
+            .unwrap();
+        assert_eq!(
+            rank,
+            database.count_votes(event.id, "guest").await.unwrap() as i32
+        );
+    }
+    database
+        .create_vote(event.id, "other", songs[2].id, event.vote_budget)
+        .await
+        .unwrap();
+
+    let too_many = database
+        .create_vote(event.id, "other", songs[0].id, 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(AppError::from(too_many), AppError::BadRequest(_)));
+
+    // votes that could not be cast anyway say why, even when the budget is spent
+    let vote_error = |err| match AppError::from(err) {
+        AppError::Vote(err) => err,
+        err => panic!("expected a vote error, got {:?}", err),
+    };
+    let voted_again = database
+        .create_vote(event.id, "guest", songs[0].id, event.vote_budget)
+        .await
+        .unwrap_err();
+    assert!(matches!(vote_error(voted_again), VoteError::AlreadyVoted));
+    let not_on_setlist = database
+        .create_vote(event.id, "guest", other_song.id, event.vote_budget)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        vote_error(not_on_setlist),
+        VoteError::NotOnSetlist
+    ));
+    let already_voted = database
+        .create_vote(event.id, "other", songs[2].id, event.vote_budget)
+        .await
+        .unwrap_err();
+    assert!(matches!(vote_error(already_voted), VoteError::AlreadyVoted));
+
+    database
+        .delete_vote(event.id, "guest", songs[0].id)
+        .await
+        .unwrap();
+    let mut votes = database.get_votes(event.id, "guest").await.unwrap();
+    votes.sort_by_key(|vote| vote.rank);
+    let ranked: Vec<_> = votes.iter().map(|vote| (vote.song_id, vote.rank)).collect();
+    assert_eq!(ranked, vec![(songs[1].id, 1), (songs[2].id, 2)]);
+
+    let not_voted = database
+        .delete_vote(event.id, "guest", songs[0].id)
+        .await
+        .unwrap_err();
+    assert!(matches!(vote_error(not_voted), VoteError::NotVoted));
+
+    assert_eq!(database.count_voters(event.id).await.unwrap(), 2);
+    assert_eq!(
+        database
+            .count_ip_sessions(event.id, "10.0.0.1", "guest")
+            .await
+            .unwrap(),
+        1
+    );
+
+    database.hide_song(songs[1].id).await.unwrap();
+    let results = database.get_vote_results(&event).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].song.id, songs[2].id);
+    assert_eq!(results[0].vote_count, 2);
+
+    database
+        .record_rejected_vote(
+            event.id,
+            "guest",
+            Some("10.0.0.1"),
+            RejectionReason::IpRateLimit,
+            2,
+        )
+        .await
+        .unwrap();
+    let rejected = database.get_rejected_votes(event.id).await.unwrap();
+    assert_eq!(rejected[0].reason, RejectionReason::IpRateLimit);
+    assert_eq!(rejected[0].count, 2);
+
+    database.admit_session(event.id, "guest").await.unwrap();
+    database.admit_session(event.id, "guest").await.unwrap();
+    assert!(database.is_admitted(event.id, "guest").await.unwrap());
+    assert!(!database.is_admitted(event.id, "other").await.unwrap());
+
+    database
+        .set_now_playing(event.id, Some(songs[2].id))
+        .await
+        .unwrap();
+    database.delete_song(songs[2].id).await.unwrap();
+    assert_eq!(
+        database
+            .get_event(event.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .now_playing,
+        None
+    );
+    assert_eq!(database.count_votes(event.id, "other").await.unwrap(), 0);
+
+    database
+        .set_voting_window(event.id, true, Some(Utc::now() - Duration::minutes(1)))
+        .await
+        .unwrap();
+    let closed = database.close_expired_voting().await.unwrap();
+    assert_eq!(closed.len(), 1);
+    assert!(!closed[0].voting_open);
+
+    database.create_admin("admin", "hash").await.unwrap();
+    let duplicate = database.create_admin("admin", "hash").await;
+    assert!(matches!(
+        duplicate.map_err(AppError::from),
+        Err(AppError::Conflict(_))
+    ));
+
+    let missing = database.hide_song(-1).await.unwrap_err();
+    assert!(matches!(AppError::from(missing), AppError::NotFound(_)));
+
+    database.delete_event(event.id).await.unwrap();
+    assert!(database
+        .get_event_by_slug("koncert")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn the_sqlite_store_behaves_like_postgres() {
+    let store = SqliteStore::connect("sqlite::memory:").await.unwrap();
+    store.migrate().await.unwrap();
+
+    behaves_like_postgres(Database::from_store(store)).await;
+}
+
+#[tokio::test]
+async fn the_memory_store_behaves_like_postgres() {
+    behaves_like_postgres(Database::from_store(MemoryStore::default())).await;
+}
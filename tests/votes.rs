@@ -0,0 +1,85 @@
+mod common;
+
+use common::event_with_songs;
+use futures::future::join_all;
+use setlist_requests::{
+    database::Database,
+    errors::{AppError, BadRequestError, VoteError},
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+#[sqlx::test]
+async fn concurrent_votes_stay_within_the_vote_budget(pool: PgPool) {
+    let database = Arc::new(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&database, "koncert", 3, 10).await;
+    database.create_session("guest", None).await.unwrap();
+
+    let votes = songs.iter().map(|song| {
+        let database = database.clone();
+        let song_id = song.id;
+        tokio::spawn(async move {
+            database
+                .create_vote(event.id, "guest", song_id, event.vote_budget)
+                .await
+        })
+    });
+    let results: Vec<_> = join_all(votes)
+        .await
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect();
+
+    let accepted = results.iter().filter(|result| result.is_ok()).count();
+    assert_eq!(accepted, 3);
+
+    for result in results.into_iter().filter_map(Result::err) {
+        assert!(matches!(
+            AppError::from(result),
+            AppError::BadRequest(BadRequestError::TooManyVotes)
+        ));
+    }
+
+    let mut ranks: Vec<i32> = database
+        .get_votes(event.id, "guest")
+        .await
+        .unwrap()
+        .iter()
+        .map(|vote| vote.rank)
+        .collect();
+    ranks.sort();
+    assert_eq!(ranks, vec![1, 2, 3]);
+}
+
+#[sqlx::test]
+async fn concurrent_votes_for_one_song_count_once(pool: PgPool) {
+    let database = Arc::new(Database::new_from_pool(pool));
+    let (event, songs) = event_with_songs(&database, "koncert", 5, 1).await;
+    database.create_session("guest", None).await.unwrap();
+
+    let song_id = songs[0].id;
+    let votes = (0..5).map(|_| {
+        let database = database.clone();
+        tokio::spawn(async move {
+            database
+                .create_vote(event.id, "guest", song_id, event.vote_budget)
+                .await
+        })
+    });
+    let results: Vec<_> = join_all(votes)
+        .await
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect();
+
+    assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+
+    for result in results.into_iter().filter_map(Result::err) {
+        assert!(matches!(
+            AppError::from(result),
+            AppError::Vote(VoteError::AlreadyVoted)
+        ));
+    }
+
+    assert_eq!(database.count_votes(event.id, "guest").await.unwrap(), 1);
+}